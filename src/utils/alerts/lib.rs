@@ -0,0 +1,159 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Delivery {
+    DirectMessage,
+    Channel(u64),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Alert {
+    pub id: u64,
+    pub user_id: u64,
+    pub coin: String,
+    /// Lowercase ISO code, as used by CoinGecko
+    pub currency: String,
+    pub direction: Direction,
+    pub threshold: Decimal,
+    pub delivery: Delivery,
+    /// Whether the next crossing should notify. Cleared when the alert fires and set
+    /// again once the price has moved back past the hysteresis band.
+    pub armed: bool,
+    /// Notifications that couldn't be delivered since the last one that was
+    #[serde(default)]
+    pub failed_deliveries: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AlertBook {
+    pub next_id: u64,
+    pub alerts: Vec<Alert>,
+}
+
+pub enum AlertEvent {
+    Triggered,
+    Rearmed,
+}
+
+impl Alert {
+    pub fn is_met(&self, price: Decimal) -> bool {
+        match self.direction {
+            Direction::Above => price >= self.threshold,
+            Direction::Below => price <= self.threshold,
+        }
+    }
+
+    /// Updates `armed` for the latest price. An alert that fired only rearms once the
+    /// price is `hysteresis` (a fraction of the threshold) back on the other side, so a
+    /// price hovering around the threshold doesn't notify over and over.
+    pub fn observe(&mut self, price: Decimal, hysteresis: Decimal) -> Option<AlertEvent> {
+        if self.armed {
+            if self.is_met(price) {
+                self.armed = false;
+                return Some(AlertEvent::Triggered);
+            }
+            return None;
+        }
+
+        let band = self.threshold * hysteresis;
+        let cleared = match self.direction {
+            Direction::Above => price < self.threshold - band,
+            Direction::Below => price > self.threshold + band,
+        };
+
+        if cleared {
+            self.armed = true;
+            return Some(AlertEvent::Rearmed);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1%, as the poller uses
+    const HYSTERESIS: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+
+    fn alert(direction: Direction) -> Alert {
+        Alert {
+            id: 1,
+            user_id: 1,
+            coin: "bitcoin".into(),
+            currency: "usd".into(),
+            direction,
+            threshold: Decimal::from(100),
+            delivery: Delivery::DirectMessage,
+            armed: true,
+            failed_deliveries: 0,
+        }
+    }
+
+    fn observe(alert: &mut Alert, price: &str) -> Option<&'static str> {
+        let price = price.parse::<Decimal>().unwrap();
+
+        match alert.observe(price, HYSTERESIS) {
+            Some(AlertEvent::Triggered) => Some("triggered"),
+            Some(AlertEvent::Rearmed) => Some("rearmed"),
+            None => None,
+        }
+    }
+
+    #[test]
+    fn above_fires_once_per_crossing() {
+        let mut alert = alert(Direction::Above);
+
+        assert_eq!(observe(&mut alert, "99"), None);
+        assert_eq!(observe(&mut alert, "100"), Some("triggered"));
+        assert!(!alert.armed);
+
+        // Staying above, or dipping back within the band, doesn't fire again
+        assert_eq!(observe(&mut alert, "105"), None);
+        assert_eq!(observe(&mut alert, "99.5"), None);
+        assert_eq!(observe(&mut alert, "101"), None);
+        assert_eq!(observe(&mut alert, "99"), None);
+
+        // Only past the band does it rearm, and then fire on the next crossing
+        assert_eq!(observe(&mut alert, "98.9"), Some("rearmed"));
+        assert!(alert.armed);
+        assert_eq!(observe(&mut alert, "100.5"), Some("triggered"));
+    }
+
+    #[test]
+    fn below_fires_once_per_crossing() {
+        let mut alert = alert(Direction::Below);
+
+        assert_eq!(observe(&mut alert, "101"), None);
+        assert_eq!(observe(&mut alert, "100"), Some("triggered"));
+
+        assert_eq!(observe(&mut alert, "90"), None);
+        assert_eq!(observe(&mut alert, "100.5"), None);
+        assert_eq!(observe(&mut alert, "101"), None);
+
+        assert_eq!(observe(&mut alert, "101.1"), Some("rearmed"));
+        assert_eq!(observe(&mut alert, "99"), Some("triggered"));
+    }
+
+    #[test]
+    fn disarmed_alerts_wait_for_the_band() {
+        // As added while the condition already holds
+        let mut alert = Alert {
+            armed: false,
+            ..alert(Direction::Above)
+        };
+
+        assert_eq!(observe(&mut alert, "120"), None);
+        assert_eq!(observe(&mut alert, "98"), Some("rearmed"));
+        assert_eq!(observe(&mut alert, "98"), None);
+        assert_eq!(observe(&mut alert, "120"), Some("triggered"));
+    }
+}
@@ -0,0 +1,246 @@
+pub mod lib;
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use rust_decimal::Decimal;
+use rusty_money::iso;
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::id::{ChannelId, UserId};
+
+use self::lib::{Alert, AlertBook, AlertEvent, Delivery, Direction};
+use super::discord::message_owner;
+use super::gecko::get_provider;
+use super::gecko::lib::Amount;
+use super::store::Store;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+// Alerts rearm once the price is 1% back on the other side of the threshold
+const HYSTERESIS: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+// Deliveries that keep failing, eg. to a user with closed DMs, are given up on after this many
+const MAX_FAILED_DELIVERIES: u32 = 10;
+
+lazy_static! {
+    static ref ALERTS: Store<AlertBook> = Store::open("alerts");
+    static ref POLLING: AtomicBool = AtomicBool::new(false);
+}
+
+/// Stores a new alert. It starts disarmed when its condition already holds, so it
+/// only fires on an actual crossing.
+pub async fn add_alert(
+    user_id: u64,
+    coin: String,
+    currency: String,
+    direction: Direction,
+    threshold: Decimal,
+    delivery: Delivery,
+    current_price: Decimal,
+) -> Result<Alert> {
+    ALERTS
+        .update(|book| {
+            book.next_id += 1;
+
+            let mut alert = Alert {
+                id: book.next_id,
+                user_id,
+                coin,
+                currency,
+                direction,
+                threshold,
+                delivery,
+                armed: true,
+                failed_deliveries: 0,
+            };
+            alert.armed = !alert.is_met(current_price);
+
+            book.alerts.push(alert.clone());
+            alert
+        })
+        .await
+}
+
+pub async fn get_alerts(user_id: u64) -> Vec<Alert> {
+    ALERTS
+        .read(|book| {
+            book.alerts
+                .iter()
+                .filter(|alert| alert.user_id == user_id)
+                .cloned()
+                .collect()
+        })
+        .await
+}
+
+/// Removes one of the user's alerts, returning whether it existed.
+pub async fn remove_alert(user_id: u64, id: u64) -> Result<bool> {
+    ALERTS
+        .update(|book| {
+            let count = book.alerts.len();
+            book.alerts
+                .retain(|alert| !(alert.id == id && alert.user_id == user_id));
+            book.alerts.len() != count
+        })
+        .await
+}
+
+/// Starts checking alerts in the background. Only the first call starts the task.
+///
+/// Failures are reported to the owner once when they start rather than on every poll.
+pub fn spawn_poller(ctx: Context) {
+    if POLLING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut failing = false;
+        let mut reported: HashSet<u64> = HashSet::new();
+
+        loop {
+            match poll(&ctx.http).await {
+                Ok(undelivered) => {
+                    failing = false;
+
+                    for (alert, err) in undelivered.iter() {
+                        if alert.failed_deliveries >= MAX_FAILED_DELIVERIES {
+                            let msg = format!(
+                                "Removed alert #{} of user {} after {} failed deliveries: {:?}",
+                                alert.id, alert.user_id, alert.failed_deliveries, err
+                            );
+                            message_owner(&ctx, msg).await;
+                        } else if reported.insert(alert.id) {
+                            let msg = format!("Failed to deliver alert #{}: {:?}", alert.id, err);
+                            message_owner(&ctx, msg).await;
+                        }
+                    }
+                    reported.retain(|id| undelivered.iter().any(|(alert, _)| alert.id == *id));
+                }
+                Err(err) => {
+                    if !failing {
+                        message_owner(&ctx, format!("Alert poll failed: {:?}", err)).await;
+                    }
+                    failing = true;
+                }
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// Checks every alert against the latest prices, returning those that couldn't be delivered
+/// with their count of failed deliveries so far.
+async fn poll(http: &Http) -> Result<Vec<(Alert, anyhow::Error)>> {
+    let (ids, currencies) = ALERTS
+        .read(|book| {
+            let ids: HashSet<String> = book.alerts.iter().map(|a| a.coin.clone()).collect();
+            let currencies: HashSet<String> =
+                book.alerts.iter().map(|a| a.currency.clone()).collect();
+            (ids, currencies)
+        })
+        .await;
+
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let ids: Vec<String> = ids.into_iter().collect();
+    let currencies: Vec<String> = currencies.into_iter().collect();
+    let prices = get_provider().get_simple_prices(&ids, &currencies).await?;
+
+    let triggered: Vec<Alert> = ALERTS
+        .update(|book| {
+            let mut triggered = vec![];
+
+            for alert in book.alerts.iter_mut() {
+                let price = prices
+                    .get(&alert.coin)
+                    .and_then(|coin_prices| coin_prices.get(&alert.currency));
+
+                if let Some(price) = price {
+                    if let Some(AlertEvent::Triggered) = alert.observe(*price, HYSTERESIS) {
+                        triggered.push(alert.clone());
+                    }
+                }
+            }
+
+            triggered
+        })
+        .await?;
+
+    let mut delivered: Vec<u64> = vec![];
+    let mut undelivered: Vec<(Alert, anyhow::Error)> = vec![];
+    for mut alert in triggered {
+        match notify(http, &alert).await {
+            Ok(_) => delivered.push(alert.id),
+            Err(err) => {
+                alert.failed_deliveries += 1;
+                undelivered.push((alert, err));
+            }
+        }
+    }
+
+    if delivered.is_empty() && undelivered.is_empty() {
+        return Ok(undelivered);
+    }
+
+    // Triggering disarmed them, re-arming lets the next poll try again while the price holds.
+    // Those that failed too often are removed instead.
+    ALERTS
+        .update(|book| {
+            book.alerts.retain(|alert| {
+                !undelivered.iter().any(|(failed, _)| {
+                    failed.id == alert.id && failed.failed_deliveries >= MAX_FAILED_DELIVERIES
+                })
+            });
+
+            for alert in book.alerts.iter_mut() {
+                if delivered.contains(&alert.id) {
+                    alert.failed_deliveries = 0;
+                }
+                if let Some((failed, _)) =
+                    undelivered.iter().find(|(failed, _)| failed.id == alert.id)
+                {
+                    alert.armed = true;
+                    alert.failed_deliveries = failed.failed_deliveries;
+                }
+            }
+        })
+        .await?;
+
+    Ok(undelivered)
+}
+
+async fn notify(http: &Http, alert: &Alert) -> Result<()> {
+    let currency = *iso::find(&alert.currency.to_uppercase()).unwrap_or(iso::USD);
+    let coin = get_provider().get_coin(&alert.coin).await?;
+
+    let direction = match alert.direction {
+        Direction::Above => "risen above",
+        Direction::Below => "fallen below",
+    };
+    let threshold = rusty_money::Money::from_decimal(alert.threshold, &currency);
+    let content = format!(
+        "🔔 **{}** has {} {} (alert #{})\nCurrent price: {}",
+        coin.name,
+        direction,
+        threshold,
+        alert.id,
+        coin.get_formatted_amount(Amount::CurrentPrice, currency)
+    );
+
+    let channel = match alert.delivery {
+        Delivery::Channel(channel_id) => ChannelId(channel_id),
+        Delivery::DirectMessage => UserId(alert.user_id).create_dm_channel(http).await?.id,
+    };
+    let content = match alert.delivery {
+        Delivery::Channel(_) => format!("<@{}> {}", alert.user_id, content),
+        Delivery::DirectMessage => content,
+    };
+
+    channel.send_message(http, |m| m.content(content)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use image::{imageops::FilterType, RgbaImage};
+use reqwest::Client;
+
+use super::gecko::cache::TtlCache;
+use super::gecko::lib::Coin;
+
+// Logos hardly ever change, a day keeps the occasional rebrand from sticking around
+const COLOR_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const LOGO_TIMEOUT: Duration = Duration::from_secs(3);
+// Logos are scaled down before sampling, the colour doesn't need every pixel
+const SAMPLE_SIZE: u32 = 64;
+const MIN_ALPHA: u8 = 200;
+// How far apart a pixel's channels need to be for it to count as coloured rather than grey
+const MIN_SATURATION: u8 = 40;
+// Fewer pixels than this are more likely stray edges than the logo itself
+const MIN_PIXELS: usize = 16;
+
+lazy_static! {
+    static ref CLIENT: Client = Client::builder()
+        .timeout(LOGO_TIMEOUT)
+        .build()
+        .expect("Error creating logo client");
+    static ref COLORS: TtlCache<Option<(u8, u8, u8)>> = TtlCache::new(COLOR_TTL);
+}
+
+/// The dominant colour of the coin's logo, or `None` when it couldn't be worked out.
+///
+/// Failures are cached as well, so a logo that is missing or slow to load is only tried
+/// once a day rather than on every price message.
+pub async fn get_coin_color(coin: &Coin) -> Option<(u8, u8, u8)> {
+    COLORS
+        .get_or_fetch(coin.id.clone(), || async {
+            Ok::<_, anyhow::Error>(fetch_color(&coin.image.large).await.ok())
+        })
+        .await
+        .ok()
+        .flatten()
+}
+
+async fn fetch_color(url: &str) -> Result<(u8, u8, u8)> {
+    let bytes = CLIENT
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let is_svg = url.split('?').next().unwrap_or_default().ends_with(".svg");
+
+    tokio::task::spawn_blocking(move || {
+        let logo = match is_svg {
+            true => rasterize_svg(&bytes)?,
+            false => image::load_from_memory(&bytes)?
+                .resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle)
+                .to_rgba8(),
+        };
+
+        get_average(&get_sample(&logo)?)
+    })
+    .await?
+}
+
+fn rasterize_svg(bytes: &[u8]) -> Result<RgbaImage> {
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default().to_ref())?;
+    let mut pixmap = tiny_skia::Pixmap::new(SAMPLE_SIZE, SAMPLE_SIZE)
+        .ok_or_else(|| anyhow!("Error creating pixmap"))?;
+
+    resvg::render(
+        &tree,
+        usvg::FitTo::Size(SAMPLE_SIZE, SAMPLE_SIZE),
+        tiny_skia::Transform::default(),
+        pixmap.as_mut(),
+    )
+    .ok_or_else(|| anyhow!("Error rendering svg"))?;
+
+    let data = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let pixel = pixel.demultiply();
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        })
+        .collect();
+
+    RgbaImage::from_raw(SAMPLE_SIZE, SAMPLE_SIZE, data)
+        .ok_or_else(|| anyhow!("Error reading rendered svg"))
+}
+
+/// The logo's opaque, coloured pixels, so transparent corners and white or black outlines
+/// don't wash out the average. Greyscale logos fall back to all their opaque pixels.
+fn get_sample(logo: &RgbaImage) -> Result<Vec<[u8; 3]>> {
+    let opaque: Vec<[u8; 3]> = logo
+        .pixels()
+        .filter(|pixel| pixel[3] >= MIN_ALPHA)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    let coloured: Vec<[u8; 3]> = opaque
+        .iter()
+        .filter(|rgb| rgb.iter().max().unwrap() - rgb.iter().min().unwrap() >= MIN_SATURATION)
+        .copied()
+        .collect();
+
+    let pixels = match coloured.len() >= MIN_PIXELS {
+        true => coloured,
+        false => opaque,
+    };
+    if pixels.len() < MIN_PIXELS {
+        return Err(anyhow!("Logo is mostly transparent"));
+    }
+
+    Ok(pixels)
+}
+
+fn get_average(pixels: &[[u8; 3]]) -> Result<(u8, u8, u8)> {
+    if pixels.is_empty() {
+        return Err(anyhow!("No colour found in logo"));
+    }
+
+    let mut sums = [0u64; 3];
+    for pixel in pixels {
+        for (sum, channel) in sums.iter_mut().zip(pixel) {
+            *sum += u64::from(*channel);
+        }
+    }
+    let count = pixels.len() as u64;
+
+    Ok((
+        (sums[0] / count) as u8,
+        (sums[1] / count) as u8,
+        (sums[2] / count) as u8,
+    ))
+}
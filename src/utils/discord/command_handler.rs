@@ -1,56 +1,209 @@
 use std::time::Instant;
 
-use crate::utils::discord::utils::message_owner;
+use rusty_money::iso;
+use tokio::sync::mpsc;
 
+use crate::utils::discord::utils::{message_owner, send_content};
+use crate::utils::gecko::lib::GeckoError;
+use crate::utils::gecko::limiter::with_retry_notice;
+use crate::utils::gecko::{get_cache_stats, get_provider, index};
+
+use super::components::{Control, View};
 use super::{commands, utils::get_invoking_user};
 use serenity::{
     client::Context,
     model::interactions::{
-        application_command::ApplicationCommandInteraction, InteractionResponseType,
+        application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
+        message_component::MessageComponentInteraction, InteractionResponseType,
     },
 };
 
+// Discord's limit on autocomplete choices
+const MAX_CHOICES: usize = 25;
+
 pub async fn handle_command(ctx: Context, command: ApplicationCommandInteraction) {
     let start = Instant::now();
     let command_name = &command.data.name;
     let user = get_invoking_user(&command);
 
+    // Acknowledge within Discord's 3 second window, the reply is filled in once ready
+    if let Err(err) = command.defer(&ctx.http).await {
+        message_owner(
+            &ctx,
+            format!("Failed to defer [{}]: {:?}", command_name, err),
+        )
+        .await;
+        return;
+    }
+
+    // Let the user know straight away when the API is throttling us
+    let mut notified = get_provider().is_busy();
+    if notified {
+        send_busy_notice(&ctx, &command).await;
+    }
+
     let command_copy = command.clone();
     let ctx_copy = ctx.clone();
 
-    let res = match command_name.as_str() {
-        "niche" => commands::niche::main(ctx_copy, command_copy).await,
-        _ => commands::coin::main(ctx_copy, command_copy).await,
+    let (notice, mut retries) = mpsc::unbounded_channel();
+    let res = with_retry_notice(notice, async move {
+        match command_copy.data.name.as_str() {
+            "alert" => commands::alert::main(ctx_copy, command_copy).await,
+            "compare" => commands::compare::main(ctx_copy, command_copy).await,
+            "niche" => commands::niche::main(ctx_copy, command_copy).await,
+            "pair" => commands::pair::main(ctx_copy, command_copy).await,
+            "portfolio" => commands::portfolio::main(ctx_copy, command_copy).await,
+            "price" => commands::price::main(ctx_copy, command_copy).await,
+            "prices" => commands::prices::main(ctx_copy, command_copy).await,
+            "theme" => commands::theme::main(ctx_copy, command_copy).await,
+            "watchlist" => commands::watchlist::main(ctx_copy, command_copy).await,
+            _ => commands::coin::main(ctx_copy, command_copy).await,
+        }
+    });
+    tokio::pin!(res);
+
+    // Or as soon as one of the command's own requests has to be retried. The command waits
+    // out the backoff meanwhile, so the notice can't overwrite its reply.
+    let res = loop {
+        tokio::select! {
+            res = &mut res => break res,
+            Some(()) = retries.recv(), if !notified => {
+                notified = true;
+                send_busy_notice(&ctx, &command).await;
+            }
+        }
     };
 
     let elapsed = start.elapsed();
 
     if let Err(err) = res {
+        let details = match err.downcast_ref::<GeckoError>() {
+            Some(gecko_err) => gecko_err.details(),
+            None => format!("{:?}", err),
+        };
+
         message_owner(
             &ctx,
             format!(
-                "Error occurred for [{}] ({:.3?}): {:?}",
-                command_name, elapsed, err
+                "Error occurred for [{}] ({:.3?}): {}",
+                command_name, elapsed, details
             ),
         )
         .await;
 
-        command
-            .create_interaction_response(&ctx.http, |r| {
-                r.kind(InteractionResponseType::ChannelMessageWithSource)
-                    .interaction_response_data(|message| message.content(format!("{}", err)))
-            })
-            .await
-            .ok();
+        send_content(&ctx, &command, format!("{}", err)).await.ok();
         return;
     }
 
+    let cache_stats = get_cache_stats();
     message_owner(
         &ctx,
         format!(
-            "{} => [{}]  success. ({:.3?} elapsed)",
-            user, command_name, elapsed
+            "{} => [{}]  success. ({:.3?} elapsed, cache: {} hits / {} misses)",
+            user, command_name, elapsed, cache_stats.hits, cache_stats.misses
         ),
     )
     .await;
 }
+
+async fn send_busy_notice(ctx: &Context, command: &ApplicationCommandInteraction) {
+    command
+        .edit_original_interaction_response(&ctx.http, |r| {
+            r.content("⏳ CoinGecko is busy right now, retrying...")
+        })
+        .await
+        .ok();
+}
+
+pub async fn handle_autocomplete(ctx: Context, autocomplete: AutocompleteInteraction) {
+    let focused = autocomplete
+        .data
+        .options
+        .iter()
+        .find(|option| option.focused);
+
+    let query = match focused.and_then(|option| option.value.as_ref()) {
+        Some(value) => value.as_str().unwrap_or_default().to_string(),
+        None => return,
+    };
+
+    let coins = index::search(&query, MAX_CHOICES);
+
+    autocomplete
+        .create_autocomplete_response(&ctx.http, |response| {
+            coins.iter().for_each(|coin| {
+                let name = format!("{} ({})", coin.name, coin.symbol.to_uppercase());
+                response.add_string_choice(name.chars().take(100).collect::<String>(), &coin.id);
+            });
+            response
+        })
+        .await
+        .ok();
+}
+
+pub async fn handle_component(ctx: Context, component: MessageComponentInteraction) {
+    let start = Instant::now();
+
+    // Controls from an older layout can't be read anymore, so only whoever clicked is told
+    let (control, mut view) = match View::from_custom_id(&component.data.custom_id) {
+        Some(parsed) => parsed,
+        None => {
+            component
+                .create_interaction_response(&ctx.http, |response| {
+                    response
+                        .kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|data| {
+                            data.content("These controls have expired, run the command again")
+                                .ephemeral(true)
+                        })
+                })
+                .await
+                .ok();
+            return;
+        }
+    };
+
+    // The currency select carries the current view, the picked currency comes with the click
+    if control == Control::Currency {
+        let picked = component
+            .data
+            .values
+            .first()
+            .and_then(|code| iso::find(code));
+
+        if let Some(currency) = picked {
+            view.currency = *currency;
+        }
+    }
+
+    if let Err(err) = component.defer(&ctx.http).await {
+        message_owner(&ctx, format!("Failed to defer [{}]: {:?}", view.coin, err)).await;
+        return;
+    }
+
+    let res = commands::coin::update(ctx.clone(), component.clone(), view.clone()).await;
+
+    let elapsed = start.elapsed();
+
+    if let Err(err) = res {
+        let details = match err.downcast_ref::<GeckoError>() {
+            Some(gecko_err) => gecko_err.details(),
+            None => format!("{:?}", err),
+        };
+
+        message_owner(
+            &ctx,
+            format!(
+                "Error occurred for [{} {:?}] ({:.3?}): {}",
+                view.coin, control, elapsed, details
+            ),
+        )
+        .await;
+
+        // Leave the message as it was and only tell whoever clicked
+        component
+            .create_followup_message(&ctx.http, |m| m.content(format!("{}", err)).ephemeral(true))
+            .await
+            .ok();
+    }
+}
@@ -0,0 +1,138 @@
+use std::slice;
+
+use anyhow::{anyhow, Result};
+use rusty_money::{iso, Money};
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::alerts::lib::{Alert, Delivery, Direction};
+use crate::utils::alerts::{add_alert, get_alerts, remove_alert};
+use crate::utils::discord::lib::CommandInfo;
+use crate::utils::discord::utils::{
+    get_coin_arg, get_command_info, get_currency_option, get_decimal_arg, resolve_coin_arg,
+    send_content,
+};
+use crate::utils::gecko::get_provider;
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+    let user_id = command.user.id.0;
+
+    let content = match command_info.get_subcommand().as_str() {
+        "add" => match add(&ctx, &command, &command_info).await? {
+            Some(content) => content,
+            None => return Ok(()),
+        },
+        "list" => list(user_id).await,
+        "remove" => {
+            let id = command_info
+                .get_arg("id")
+                .and_then(|id| id.as_u64())
+                .ok_or_else(|| anyhow!("Which alert? Use `/alert list` to see their ids"))?;
+
+            match remove_alert(user_id, id).await? {
+                true => format!("🗑️ Removed alert #{}", id),
+                false => format!("You don't have an alert #{}", id),
+            }
+        }
+        other => return Err(anyhow!("Unknown alert command: {}", other)),
+    };
+
+    send_content(&ctx, &command, content).await
+}
+
+async fn add(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    command_info: &CommandInfo<'_>,
+) -> Result<Option<String>> {
+    let (direction, threshold) = match (
+        get_decimal_arg(command_info, "above"),
+        get_decimal_arg(command_info, "below"),
+    ) {
+        (Some(above), None) => (Direction::Above, above),
+        (None, Some(below)) => (Direction::Below, below),
+        _ => return Err(anyhow!("Set exactly one of `above` or `below`")),
+    };
+
+    let coin_id = match resolve_coin_arg(ctx, command, &get_coin_arg(command_info, "coin")).await? {
+        Some(coin_id) => coin_id,
+        None => return Ok(None),
+    };
+
+    let currency = get_currency_option(command)?;
+    let currency_code = currency.iso_alpha_code.to_lowercase();
+    let prices = get_provider()
+        .get_simple_prices(slice::from_ref(&coin_id), slice::from_ref(&currency_code))
+        .await?;
+    let current_price = prices
+        .get(&coin_id)
+        .and_then(|coin_prices| coin_prices.get(&currency_code))
+        .copied()
+        .ok_or_else(|| {
+            anyhow!(
+                "No {} price available for {}",
+                currency.iso_alpha_code,
+                coin_id
+            )
+        })?;
+
+    let delivery = match command_info
+        .get_arg("notify")
+        .as_ref()
+        .and_then(|n| n.as_str())
+    {
+        Some("channel") => Delivery::Channel(command.channel_id.0),
+        _ => Delivery::DirectMessage,
+    };
+
+    let alert = add_alert(
+        command.user.id.0,
+        coin_id,
+        currency_code,
+        direction,
+        threshold,
+        delivery,
+        current_price,
+    )
+    .await?;
+
+    Ok(Some(format!(
+        "✅ {}\nCurrent price: {}",
+        describe(&alert),
+        Money::from_decimal(current_price, &currency)
+    )))
+}
+
+async fn list(user_id: u64) -> String {
+    let alerts = get_alerts(user_id).await;
+
+    if alerts.is_empty() {
+        return "You have no alerts. Add one with `/alert add`".into();
+    }
+
+    let lines: Vec<String> = alerts.iter().map(describe).collect();
+    format!("__Your alerts__\n{}", lines.join("\n"))
+}
+
+fn describe(alert: &Alert) -> String {
+    let currency = *iso::find(&alert.currency.to_uppercase()).unwrap_or(iso::USD);
+    let direction = match alert.direction {
+        Direction::Above => "above",
+        Direction::Below => "below",
+    };
+    let delivery = match alert.delivery {
+        Delivery::DirectMessage => "by DM".to_string(),
+        Delivery::Channel(channel_id) => format!("in <#{}>", channel_id),
+    };
+
+    format!(
+        "#{} • {} {} {}, notifying {}",
+        alert.id,
+        alert.coin,
+        direction,
+        Money::from_decimal(alert.threshold, &currency),
+        delivery
+    )
+}
@@ -1,60 +1,121 @@
-use std::fs;
-
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use rusty_money::iso::Currency;
-use serenity::model::interactions::InteractionResponseType;
+use serenity::builder::CreateEmbed;
 use serenity::model::Timestamp;
-use serenity::utils::Colour;
 use serenity::{
-    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+    client::Context,
+    model::interactions::{
+        application_command::ApplicationCommandInteraction,
+        message_component::MessageComponentInteraction,
+    },
 };
-use tokio::sync::mpsc::{self, Receiver, Sender};
 
-use crate::utils::discord::utils::{get_currency_option, get_graph_option};
-use crate::utils::gecko::get_ohlc;
-use crate::utils::gecko::lib::{Amount, Coin};
-use crate::utils::gecko::{get_coin, lib::MarketChange};
-use crate::utils::plotter::{get_line_chart, get_ohlc_chart};
+use crate::utils::branding::get_coin_color;
+use crate::utils::discord::components::{build_components, View};
+use crate::utils::discord::lib::Attachment;
+use crate::utils::discord::utils::{
+    get_currency_option, get_embed_colour, get_export_option, get_graph_option,
+    get_indicators_option, get_timeframe_option, send_embed, update_embed,
+};
+use crate::utils::export::{
+    get_market_chart_sheet, get_ohlc_sheet, get_sparkline_sheet, Export, Sheet,
+};
+use crate::utils::gecko::get_provider;
+use crate::utils::gecko::lib::{Amount, Coin, MarketChange, MarketChart};
+use crate::utils::plotter::theme::Theme;
+use crate::utils::plotter::{get_line_chart, get_ohlc_chart, Chart, Quote};
+use crate::utils::settings::{get_guild_settings, GuildSettings};
 
 pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
-    let command_name = command.data.name.clone();
-    let command_name1 = command_name.clone();
-
-    let currency = get_currency_option(&command)?;
-    let graph = get_graph_option(&command)?;
-    let (tx, rx): (Sender<Coin>, Receiver<Coin>) = mpsc::channel(1);
-    let graph_handle = tokio::spawn(async move { build_graph(rx, &command_name, graph).await });
-
-    let coin = get_coin(&command_name1).await?;
-    tx.send(coin.clone()).await.ok();
-
-    let message_handle = tokio::spawn(async move { build_message(coin, currency).await });
-
-    let (title, title_url, description, thumbnail, fields) = message_handle.await??;
-    let filename = graph_handle.await??;
-    let attachment = format!("attachment://{}", filename);
-
-    command
-        .create_interaction_response(&ctx.http, |r| {
-            r.kind(InteractionResponseType::ChannelMessageWithSource)
-                .interaction_response_data(|message| {
-                    message
-                        .embed(|e| {
-                            e.author(|a| a.icon_url(thumbnail).name(title).url(title_url))
-                                .description(description)
-                                .fields(fields)
-                                .color(Colour::DARK_GOLD)
-                                .timestamp(Timestamp::now())
-                                .image(attachment)
-                        })
-                        .add_file(filename.as_str())
-                })
-        })
-        .await?;
-
-    fs::remove_file(filename)?;
-
-    Ok(())
+    let coin_id = command.data.name.clone();
+
+    respond(ctx, command, coin_id).await
+}
+
+/// Replies with the price embed and chart for `coin_id`.
+pub async fn respond(
+    ctx: Context,
+    command: ApplicationCommandInteraction,
+    coin_id: String,
+) -> Result<()> {
+    let view = View {
+        coin: coin_id,
+        graph: get_graph_option(&command)?,
+        timeframe: get_timeframe_option(&command)?,
+        currency: get_currency_option(&command)?,
+        indicators: get_indicators_option(&command)?,
+        export: get_export_option(&command)?,
+    };
+    let settings = get_guild_settings(command.guild_id.map(|id| id.0)).await;
+
+    let (embed, attachments) = render(&view, settings).await?;
+
+    send_embed(&ctx, &command, embed, build_components(&view), attachments).await
+}
+
+/// Re-renders a price message in place after one of its controls was used.
+pub async fn update(
+    ctx: Context,
+    component: MessageComponentInteraction,
+    view: View,
+) -> Result<()> {
+    let settings = get_guild_settings(component.guild_id.map(|id| id.0)).await;
+    let (embed, attachments) = render(&view, settings).await?;
+
+    update_embed(
+        &ctx,
+        &component,
+        embed,
+        build_components(&view),
+        attachments,
+    )
+    .await
+}
+
+/// Builds the price embed, the chart it shows and any export of its data.
+async fn render(view: &View, settings: GuildSettings) -> Result<(CreateEmbed, Vec<Attachment>)> {
+    // The logo's colour brands the embed, and the chart too when the guild wants it. It only
+    // needs the coin's logo, so it's fetched while the graph data is still loading
+    let ((coin, color), data) = tokio::try_join!(
+        async {
+            let coin = get_provider().get_coin(&view.coin).await?;
+            let color = get_coin_color(&coin).await;
+            Ok::<_, anyhow::Error>((coin, color))
+        },
+        fetch_graph_data(view)
+    )?;
+
+    let sheet = match view.export {
+        Some(Export::Chart) => Some(get_graph_sheet(&view.coin, &data)?),
+        Some(Export::Sparkline) => {
+            let sparkline = (
+                coin.symbol.to_uppercase(),
+                coin.market_data.sparkline_7d.price.clone(),
+            );
+            Some(get_sparkline_sheet(&view.coin, &[sparkline])?)
+        }
+        None => None,
+    };
+
+    let chart = draw_graph(view.clone(), data, settings.get_theme(color)).await?;
+
+    let (title, title_url, description, thumbnail, fields) =
+        build_message(coin, view.currency).await?;
+    let attachment = format!("attachment://{}", chart.filename);
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .author(|a| a.icon_url(thumbnail).name(title).url(title_url))
+        .description(description)
+        .fields(fields)
+        .color(get_embed_colour(color))
+        .timestamp(Timestamp::now())
+        .image(attachment);
+
+    let mut attachments: Vec<Attachment> = vec![chart.into()];
+    attachments.extend(sheet.map(Attachment::from));
+
+    Ok((embed, attachments))
 }
 
 async fn build_message(
@@ -107,16 +168,76 @@ async fn build_message(
     Ok((title, title_url, description, thumbnail, fields))
 }
 
-async fn build_graph(mut rx: Receiver<Coin>, coin_name: &str, graph: String) -> Result<String> {
-    match graph.as_str() {
-        "line" => {
-            let coin = rx.recv().await.unwrap();
-            get_line_chart(&coin)
-        }
+/// Market data for the graph, fetched alongside the coin.
+enum GraphData {
+    Line(MarketChart),
+    /// Candles don't come with volumes, those are taken from the market chart
+    Ohlc(Vec<Vec<f64>>, MarketChart),
+}
+
+async fn fetch_graph_data(view: &View) -> Result<GraphData> {
+    let (coin, timeframe, currency) = (&view.coin, view.timeframe, view.currency);
+
+    match view.graph.as_str() {
+        "line" => Ok(GraphData::Line(
+            get_provider()
+                .get_market_chart(coin, timeframe, currency)
+                .await?,
+        )),
         "ohlc" => {
-            let ohlc_data = get_ohlc(&coin_name).await?;
-            get_ohlc_chart(&ohlc_data, &coin_name)
+            let (data, market_chart) = tokio::try_join!(
+                get_provider().get_ohlc(coin, timeframe, currency),
+                get_provider().get_market_chart(coin, timeframe, currency)
+            )?;
+            Ok(GraphData::Ohlc(data, market_chart))
         }
-        _ => Ok("".into()),
+        other => Err(anyhow!("Unknown graph type: {}", other)),
     }
 }
+
+/// The rows the graph is drawn from, as CSV.
+fn get_graph_sheet(coin: &str, data: &GraphData) -> Result<Sheet> {
+    match data {
+        GraphData::Line(market_chart) => get_market_chart_sheet(
+            coin,
+            &market_chart.prices,
+            &market_chart.market_caps,
+            &market_chart.total_volumes,
+        ),
+        GraphData::Ohlc(candles, _) => get_ohlc_sheet(coin, candles),
+    }
+}
+
+async fn draw_graph(view: View, data: GraphData, theme: Theme) -> Result<Chart> {
+    let View {
+        coin,
+        currency,
+        indicators,
+        ..
+    } = view;
+
+    // Drawing and encoding are CPU bound, so they're kept off the async workers
+    tokio::task::spawn_blocking(move || {
+        let quote = Quote::Currency(&currency);
+
+        match data {
+            GraphData::Line(market_chart) => get_line_chart(
+                &market_chart.prices,
+                &market_chart.total_volumes,
+                &coin,
+                &quote,
+                &indicators,
+                &theme,
+            ),
+            GraphData::Ohlc(data, market_chart) => get_ohlc_chart(
+                &data,
+                &market_chart.total_volumes,
+                &coin,
+                &quote,
+                &indicators,
+                &theme,
+            ),
+        }
+    })
+    .await?
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use rusty_money::iso::Currency;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::Timestamp;
+use serenity::utils::Colour;
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::discord::lib::Attachment;
+use crate::utils::discord::utils::{
+    get_coins_arg, get_command_info, get_currency_option, get_export_option, get_timeframe_option,
+    resolve_coin_arg, send_embed,
+};
+use crate::utils::export::{get_series_sheet, get_sparkline_sheet, Export};
+use crate::utils::gecko::get_provider;
+use crate::utils::gecko::lib::{Amount, Coin, MarketChange};
+use crate::utils::plotter::get_comparison_chart;
+use crate::utils::settings::get_guild_settings;
+
+// Each coin gets its own colour on the chart
+const MAX_COINS: usize = 5;
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+    let queries = get_coins_arg(&command_info);
+
+    if queries.len() > MAX_COINS {
+        return Err(anyhow!("Compare at most {} coins at a time", MAX_COINS));
+    }
+
+    let mut coin_ids: Vec<String> = vec![];
+    for query in queries.iter() {
+        match resolve_coin_arg(&ctx, &command, query).await? {
+            Some(coin_id) if !coin_ids.contains(&coin_id) => coin_ids.push(coin_id),
+            Some(_) => {}
+            None => return Ok(()),
+        }
+    }
+
+    if coin_ids.len() < 2 {
+        return Err(anyhow!(
+            "Give at least 2 different coins, eg. `solana,ethereum,bitcoin`"
+        ));
+    }
+
+    let currency = get_currency_option(&command)?;
+    let timeframe = get_timeframe_option(&command)?;
+    let export = get_export_option(&command)?;
+
+    // Every coin is fetched at once
+    let handles: Vec<_> = coin_ids
+        .into_iter()
+        .map(|coin_id| {
+            tokio::spawn(async move {
+                tokio::try_join!(
+                    get_provider().get_coin(&coin_id),
+                    get_provider().get_market_chart(&coin_id, timeframe, currency)
+                )
+            })
+        })
+        .collect();
+
+    let mut coins = vec![];
+    for handle in handles {
+        coins.push(handle.await??);
+    }
+
+    let series: Vec<(String, Vec<(f64, f64)>)> = coins
+        .iter()
+        .map(|(coin, market_chart)| (coin.symbol.to_uppercase(), market_chart.prices.clone()))
+        .collect();
+    let name = coins
+        .iter()
+        .map(|(coin, _)| coin.id.as_str())
+        .collect::<Vec<&str>>()
+        .join("_");
+    // Each coin already has its own colour, so only the guild's palette applies
+    let theme = get_guild_settings(command.guild_id.map(|id| id.0))
+        .await
+        .get_theme(None);
+
+    let sheet = match export {
+        Some(Export::Chart) => Some(get_series_sheet(&name, &series)?),
+        Some(Export::Sparkline) => {
+            let sparklines: Vec<(String, Vec<f64>)> = coins
+                .iter()
+                .map(|(coin, _)| {
+                    let prices = coin.market_data.sparkline_7d.price.clone();
+                    (coin.symbol.to_uppercase(), prices)
+                })
+                .collect();
+            Some(get_sparkline_sheet(&name, &sparklines)?)
+        }
+        None => None,
+    };
+    let chart =
+        tokio::task::spawn_blocking(move || get_comparison_chart(&series, &name, &theme)).await??;
+
+    let title = coins
+        .iter()
+        .map(|(coin, _)| coin.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(" vs ");
+    let fields: Vec<(String, String, bool)> = coins
+        .iter()
+        .map(|(coin, _)| (coin.name.clone(), get_summary(coin, currency), true))
+        .collect();
+    let attachment = format!("attachment://{}", chart.filename);
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(title)
+        .description(format!(
+            "Change in price over the {} timeframe",
+            timeframe.label()
+        ))
+        .fields(fields)
+        .color(Colour::DARK_GOLD)
+        .timestamp(Timestamp::now())
+        .image(attachment);
+
+    let mut attachments: Vec<Attachment> = vec![chart.into()];
+    attachments.extend(sheet.map(Attachment::from));
+
+    send_embed(
+        &ctx,
+        &command,
+        embed,
+        CreateComponents::default(),
+        attachments,
+    )
+    .await
+}
+
+/// A column of the comparison table.
+fn get_summary(coin: &Coin, currency: Currency) -> String {
+    format!(
+        "**Price**\n{}**Market Cap**\n{}**24h**\n{}**7d**\n{}",
+        coin.get_formatted_amount(Amount::CurrentPrice, currency),
+        coin.get_formatted_amount(Amount::MarketCap, currency),
+        coin.get_formatted_change(MarketChange::PercentageChange24h, currency),
+        coin.get_formatted_change(MarketChange::PercentageChange7d, currency),
+    )
+}
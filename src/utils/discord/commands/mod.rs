@@ -1,2 +1,10 @@
+pub mod alert;
 pub mod coin;
+pub mod compare;
 pub mod niche;
+pub mod pair;
+pub mod portfolio;
+pub mod price;
+pub mod prices;
+pub mod theme;
+pub mod watchlist;
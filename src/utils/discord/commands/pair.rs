@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Result};
+use rusty_money::iso;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::Timestamp;
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::branding::get_coin_color;
+use crate::utils::discord::lib::Attachment;
+use crate::utils::discord::utils::{
+    get_coin_arg, get_command_info, get_embed_colour, get_export_option, get_graph_option,
+    get_timeframe_option, resolve_coin_arg, send_embed,
+};
+use crate::utils::export::{get_ohlc_sheet, get_series_sheet};
+use crate::utils::gecko::get_provider;
+use crate::utils::gecko::lib::MarketChart;
+use crate::utils::plotter::{format_value, get_line_chart, get_ohlc_chart, Quote};
+use crate::utils::settings::get_guild_settings;
+
+// Roughly how many candles a synthesized OHLC chart is split into
+const CANDLE_COUNT: usize = 60;
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+
+    let base_id =
+        match resolve_coin_arg(&ctx, &command, &get_coin_arg(&command_info, "base")).await? {
+            Some(coin_id) => coin_id,
+            None => return Ok(()),
+        };
+    let quote_id =
+        match resolve_coin_arg(&ctx, &command, &get_coin_arg(&command_info, "quote")).await? {
+            Some(coin_id) => coin_id,
+            None => return Ok(()),
+        };
+
+    if base_id == quote_id {
+        return Err(anyhow!("Pick two different coins"));
+    }
+
+    let timeframe = get_timeframe_option(&command)?;
+    let graph = get_graph_option(&command)?;
+    // Both sides are priced in the same currency, so it cancels out of the ratio
+    let currency = *iso::USD;
+
+    let (base, quote, base_chart, quote_chart) = tokio::try_join!(
+        get_provider().get_coin(&base_id),
+        get_provider().get_coin(&quote_id),
+        get_provider().get_market_chart(&base_id, timeframe, currency),
+        get_provider().get_market_chart(&quote_id, timeframe, currency)
+    )?;
+
+    let ratios = get_ratios(&base_chart.prices, &quote_chart);
+    if ratios.len() < 2 {
+        return Err(anyhow!(
+            "{} and {} don't have enough price history in common",
+            base.name,
+            quote.name
+        ));
+    }
+
+    let symbol = quote.symbol.to_uppercase();
+    let (first, last) = (ratios[0].1, ratios[ratios.len() - 1].1);
+    let change = (last / first - 1.0) * 100.0;
+
+    // The pair takes after its base coin
+    let color = get_coin_color(&base).await;
+    let theme = get_guild_settings(command.guild_id.map(|id| id.0))
+        .await
+        .get_theme(color);
+
+    let name = format!("{}_{}", base.id, quote.id);
+    let sheet = match (get_export_option(&command)?, graph.as_str()) {
+        (None, _) => None,
+        (Some(_), "ohlc") => Some(get_ohlc_sheet(&name, &get_candles(&ratios))?),
+        (Some(_), _) => {
+            let label = format!("{}/{}", base.symbol.to_uppercase(), symbol);
+            Some(get_series_sheet(&name, &[(label, ratios.clone())])?)
+        }
+    };
+    let chart_symbol = symbol.clone();
+    let chart = tokio::task::spawn_blocking(move || {
+        let quote = Quote::Coin(&chart_symbol);
+
+        match graph.as_str() {
+            "ohlc" => get_ohlc_chart(&get_candles(&ratios), &[], &name, &quote, &[], &theme),
+            _ => get_line_chart(&ratios, &[], &name, &quote, &[], &theme),
+        }
+    })
+    .await??;
+
+    let prefix = if change.is_sign_positive() { "+" } else { "" };
+    let fields: Vec<(String, String, bool)> = vec![
+        (
+            "Ratio".into(),
+            format!("```{} {}```", format_value(last), symbol),
+            true,
+        ),
+        (
+            format!("{} Change", timeframe.label()),
+            format!("```diff\n{}{:.1}%```", prefix, change),
+            true,
+        ),
+    ];
+    let attachment = format!("attachment://{}", chart.filename);
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .author(|a| {
+            a.icon_url(base.image.large.clone()).name(format!(
+                "{}/{}",
+                base.symbol.to_uppercase(),
+                symbol
+            ))
+        })
+        .description(format!("{} priced in {}", base.name, quote.name))
+        .fields(fields)
+        .color(get_embed_colour(color))
+        .timestamp(Timestamp::now())
+        .image(attachment);
+
+    let mut attachments: Vec<Attachment> = vec![chart.into()];
+    attachments.extend(sheet.map(Attachment::from));
+
+    send_embed(
+        &ctx,
+        &command,
+        embed,
+        CreateComponents::default(),
+        attachments,
+    )
+    .await
+}
+
+/// Divides each base price by the quote's price at the same moment.
+fn get_ratios(base: &[(f64, f64)], quote: &MarketChart) -> Vec<(f64, f64)> {
+    base.iter()
+        .filter_map(|(time, price)| {
+            let quote_price = quote.price_at(*time)?;
+
+            match quote_price > 0.0 {
+                true => Some((*time, price / quote_price)),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+/// Groups the ratios into `[timestamp, open, high, low, close]` candles.
+fn get_candles(ratios: &[(f64, f64)]) -> Vec<Vec<f64>> {
+    let per_candle = ratios.len().div_ceil(CANDLE_COUNT).max(1);
+
+    ratios
+        .chunks(per_candle)
+        .map(|chunk| {
+            let (high, low) = chunk
+                .iter()
+                .fold((f64::MIN, f64::MAX), |(high, low), (_, ratio)| {
+                    (high.max(*ratio), low.min(*ratio))
+                });
+
+            vec![chunk[0].0, chunk[0].1, high, low, chunk[chunk.len() - 1].1]
+        })
+        .collect()
+}
@@ -0,0 +1,763 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::slice;
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rusty_money::iso::{self, Currency};
+use rusty_money::Money;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::Timestamp;
+use serenity::utils::Colour;
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::discord::lib::{Attachment, CommandInfo};
+use crate::utils::discord::utils::{
+    get_coin_arg, get_command_info, get_currency_option, get_decimal_arg, get_export_option,
+    get_timeframe_option, resolve_coin_arg, send_content, send_embed, send_file, send_text_embed,
+};
+use crate::utils::export::get_series_sheet;
+use crate::utils::gecko::lib::MarketChart;
+use crate::utils::gecko::resolver::{resolve_coin, Resolution};
+use crate::utils::gecko::{get_provider, index};
+use crate::utils::plotter::get_portfolio_chart;
+use crate::utils::portfolio::ledger::{Book, CostMethod};
+use crate::utils::portfolio::lib::{Holding, Portfolio, Transaction, TransactionKind};
+use crate::utils::portfolio::sheet::{get_ledger_sheet, read_rows, Columns, Row};
+use crate::utils::portfolio::{
+    delete_transaction, get_holding, get_portfolio, import_transactions, record_transaction,
+};
+use crate::utils::settings::get_guild_settings;
+
+// Discord allows 25 fields, the totals take the first 3
+const MAX_HOLDING_FIELDS: usize = 21;
+// Keeps the list within Discord's 2000 character limit
+const MAX_LISTED_TRANSACTIONS: usize = 20;
+const MAX_IMPORT_SIZE: u64 = 1024 * 1024;
+const MAX_IMPORTED_ROWS: usize = 5000;
+
+/// A holding priced at the current market.
+struct Position {
+    holding: Holding,
+    symbol: String,
+    price: Option<Decimal>,
+}
+
+impl Position {
+    fn value(&self) -> Option<Decimal> {
+        Some(self.price? * self.holding.amount)
+    }
+}
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+    let user_id = command.user.id.0;
+
+    match command_info.get_subcommand().as_str() {
+        "add" | "tx buy" => {
+            record(&ctx, &command, &command_info, user_id, TransactionKind::Buy).await
+        }
+        "tx sell" => {
+            record(
+                &ctx,
+                &command,
+                &command_info,
+                user_id,
+                TransactionKind::Sell,
+            )
+            .await
+        }
+        "remove" => {
+            let kind = TransactionKind::TransferOut;
+            record(&ctx, &command, &command_info, user_id, kind).await
+        }
+        "tx transfer" => {
+            let kind = match command_info
+                .get_arg("direction")
+                .as_ref()
+                .and_then(|d| d.as_str())
+            {
+                Some("in") => TransactionKind::TransferIn,
+                _ => TransactionKind::TransferOut,
+            };
+            record(&ctx, &command, &command_info, user_id, kind).await
+        }
+        "tx list" => list(&ctx, &command, user_id).await,
+        "tx delete" => {
+            let id = command_info
+                .get_arg("id")
+                .and_then(|id| id.as_u64())
+                .ok_or_else(|| {
+                    anyhow!("Which transaction? Use `/portfolio tx list` to see their ids")
+                })?;
+
+            let content = match delete_transaction(user_id, id).await? {
+                true => format!("🗑️ Deleted transaction #{}", id),
+                false => format!("You don't have a transaction #{}", id),
+            };
+            send_content(&ctx, &command, content).await
+        }
+        "view" => view(&ctx, &command, user_id).await,
+        "report" => report(&ctx, &command, &command_info, user_id).await,
+        "import" => import(&ctx, &command, &command_info, user_id).await,
+        "export" => export(&ctx, &command, user_id).await,
+        other => Err(anyhow!("Unknown portfolio command: {}", other)),
+    }
+}
+
+/// Records a transaction from the command's options. Transfers out without an amount move
+/// the whole holding.
+async fn record(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    command_info: &CommandInfo<'_>,
+    user_id: u64,
+    kind: TransactionKind,
+) -> Result<()> {
+    let amount = get_decimal_arg(command_info, "amount");
+    if amount.is_some_and(|amount| amount <= Decimal::ZERO) {
+        return Err(anyhow!("The amount has to be more than 0"));
+    }
+
+    let coin_id = match resolve_coin_arg(ctx, command, &get_coin_arg(command_info, "coin")).await? {
+        Some(coin_id) => coin_id,
+        None => return Ok(()),
+    };
+
+    let amount = match (amount, kind) {
+        (Some(amount), _) => amount,
+        (None, TransactionKind::TransferOut) => match get_holding(user_id, &coin_id).await? {
+            Some(holding) => holding.amount,
+            None => {
+                let content = format!("You don't hold any {}", coin_id);
+                return send_content(ctx, command, content).await;
+            }
+        },
+        (None, _) => return Err(anyhow!("How many coins?")),
+    };
+
+    let currency = get_record_currency(command, command_info, user_id).await?;
+    let currency_code = currency.iso_alpha_code.to_lowercase();
+
+    let price = match (get_decimal_arg(command_info, "price"), kind) {
+        (Some(price), _) => price,
+        (None, TransactionKind::TransferOut) => Decimal::ZERO,
+        (None, TransactionKind::TransferIn) => get_current_price(&coin_id, &currency_code).await?,
+        (None, _) => return Err(anyhow!("What was the price per coin?")),
+    };
+    let time = get_time_arg(command_info)?;
+
+    let tx =
+        record_transaction(user_id, kind, &coin_id, amount, price, time, &currency_code).await?;
+
+    let holding = match get_holding(user_id, &coin_id).await? {
+        Some(holding) => format!(
+            "You now hold {} {}, at an average of {}",
+            holding.amount.normalize(),
+            coin_id,
+            Money::from_decimal(holding.average_price().round_dp(8), &currency)
+        ),
+        None => format!("You no longer hold any {}", coin_id),
+    };
+    let content = format!("✅ {}\n{}", describe(&tx, &currency), holding);
+
+    send_content(ctx, command, content).await
+}
+
+async fn list(ctx: &Context, command: &ApplicationCommandInteraction, user_id: u64) -> Result<()> {
+    let portfolio = get_portfolio(user_id).await;
+    let currency = get_currency(&portfolio);
+
+    if portfolio.transactions.is_empty() {
+        let content = "You have no transactions. Record one with `/portfolio tx buy`".into();
+        return send_content(ctx, command, content).await;
+    }
+
+    let mut transactions = portfolio.transactions.clone();
+    transactions.sort_by_key(|tx| Reverse((tx.time, tx.id)));
+
+    let lines: Vec<String> = transactions
+        .iter()
+        .take(MAX_LISTED_TRANSACTIONS)
+        .map(|tx| describe(tx, &currency))
+        .collect();
+    let more = match transactions.len() > MAX_LISTED_TRANSACTIONS {
+        true => format!(
+            "\n…and {} older",
+            transactions.len() - MAX_LISTED_TRANSACTIONS
+        ),
+        false => "".into(),
+    };
+
+    let content = format!("__Your transactions__\n{}{}", lines.join("\n"), more);
+    send_content(ctx, command, content).await
+}
+
+/// Realized and unrealized profit per coin, under the chosen cost method.
+async fn report(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    command_info: &CommandInfo<'_>,
+    user_id: u64,
+) -> Result<()> {
+    let portfolio = get_portfolio(user_id).await;
+    if portfolio.transactions.is_empty() {
+        let content = "You have no transactions. Record one with `/portfolio tx buy`".into();
+        return send_content(ctx, command, content).await;
+    }
+
+    let method = command_info
+        .get_arg("method")
+        .and_then(|method| method.as_str().and_then(CostMethod::from_name))
+        .unwrap_or(CostMethod::Fifo);
+    let currency = get_currency(&portfolio);
+    let books = portfolio.get_books(method)?;
+
+    let ids: Vec<String> = books.iter().map(|(coin, _)| coin.clone()).collect();
+    let prices = get_provider()
+        .get_simple_prices(&ids, slice::from_ref(&portfolio.currency))
+        .await?;
+    let get_price = |coin: &String| {
+        prices
+            .get(coin)
+            .and_then(|coin_prices| coin_prices.get(&portfolio.currency))
+            .copied()
+    };
+
+    let (mut realized, mut unrealized, mut cost) = (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+    let mut fields: Vec<(String, String, bool)> = vec![];
+
+    for (coin, book) in books.iter() {
+        let price = get_price(coin);
+        realized += book.realized;
+        if let Some(price) = price {
+            unrealized += book.unrealized(price);
+            cost += book.cost();
+        }
+
+        if fields.len() < MAX_HOLDING_FIELDS {
+            fields.push((coin.clone(), get_book_summary(book, price, &currency), true));
+        }
+    }
+
+    let mut totals: Vec<(String, String, bool)> = vec![
+        (
+            "Realized P&L".into(),
+            format_profit(realized, Decimal::ZERO, &currency),
+            true,
+        ),
+        (
+            "Unrealized P&L".into(),
+            format_profit(unrealized, cost, &currency),
+            true,
+        ),
+        (
+            "Cost Basis".into(),
+            format!("```{}```", Money::from_decimal(cost.round_dp(2), &currency)),
+            true,
+        ),
+    ];
+    totals.extend(fields);
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .author(|a| {
+            a.icon_url(command.user.face())
+                .name(format!("{}'s P&L report", command.user.name))
+        })
+        .description(format!(
+            "Lots matched with {} over {} transactions",
+            method.label(),
+            portfolio.transactions.len()
+        ))
+        .fields(totals)
+        .color(Colour::DARK_GOLD)
+        .timestamp(Timestamp::now());
+
+    send_text_embed(ctx, command, embed).await
+}
+
+/// Adds the transactions of an attached CSV file, eg. a spreadsheet or an exchange's export.
+async fn import(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    command_info: &CommandInfo<'_>,
+    user_id: u64,
+) -> Result<()> {
+    let attachment = command
+        .data
+        .resolved
+        .attachments
+        .values()
+        .next()
+        .ok_or_else(|| anyhow!("Attach the CSV file to import"))?;
+    if attachment.size > MAX_IMPORT_SIZE {
+        return Err(anyhow!("Files can be at most 1 MB"));
+    }
+
+    let data = attachment.download().await?;
+    let mut rows = read_rows(&data, &get_columns_arg(command_info))?;
+    if rows.is_empty() {
+        let content = format!("{} has no transactions in it", attachment.filename);
+        return send_content(ctx, command, content).await;
+    }
+    if rows.len() > MAX_IMPORTED_ROWS {
+        return Err(anyhow!(
+            "Import at most {} transactions at a time",
+            MAX_IMPORTED_ROWS
+        ));
+    }
+
+    // Nobody is around to pick from candidates, so the coins have to be unambiguous
+    let mut coin_ids: HashMap<String, String> = HashMap::new();
+    for row in rows.iter_mut() {
+        let query = row.coin.to_lowercase();
+        let coin_id = match coin_ids.get(&query) {
+            Some(coin_id) => coin_id.clone(),
+            None => match resolve_coin(get_provider(), &query).await? {
+                Resolution::Found(info) => info.id,
+                Resolution::Ambiguous(_) => {
+                    return Err(anyhow!(
+                        "Line {}: `{}` could be several coins, use its CoinGecko id instead",
+                        row.line,
+                        row.coin
+                    ))
+                }
+                Resolution::NotFound => {
+                    return Err(anyhow!("Line {}: no coin called `{}`", row.line, row.coin))
+                }
+            },
+        };
+
+        coin_ids.insert(query, coin_id.clone());
+        row.coin = coin_id;
+    }
+
+    let currency_code = get_import_currency(command, command_info, user_id, &rows).await?;
+    let count = import_transactions(user_id, rows, &currency_code).await?;
+    let content = format!(
+        "📥 Imported {} transactions from {}\nSee them with `/portfolio tx list`",
+        count, attachment.filename
+    );
+
+    send_content(ctx, command, content).await
+}
+
+/// Attaches the ledger as a CSV file, in the format `/portfolio import` reads by default.
+async fn export(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    user_id: u64,
+) -> Result<()> {
+    let portfolio = get_portfolio(user_id).await;
+    if portfolio.transactions.is_empty() {
+        let content = "You have no transactions. Record one with `/portfolio tx buy`".into();
+        return send_content(ctx, command, content).await;
+    }
+
+    let sheet = get_ledger_sheet(&format!("portfolio_{}", user_id), &portfolio)?;
+    let content = format!(
+        "📤 Your {} transactions, priced in {}",
+        portfolio.transactions.len(),
+        portfolio.currency.to_uppercase()
+    );
+
+    send_file(ctx, command, content, sheet.into()).await
+}
+
+async fn view(ctx: &Context, command: &ApplicationCommandInteraction, user_id: u64) -> Result<()> {
+    let portfolio = get_portfolio(user_id).await;
+    if portfolio.get_holdings(CostMethod::Fifo)?.is_empty() {
+        let content = "Your portfolio is empty. Add to it with `/portfolio add`".into();
+        return send_content(ctx, command, content).await;
+    }
+
+    let currency = get_currency(&portfolio);
+    let timeframe = get_timeframe_option(command)?;
+    let holdings = portfolio.get_holdings(CostMethod::Fifo)?;
+    let ids: Vec<String> = holdings
+        .iter()
+        .map(|holding| holding.coin.clone())
+        .collect();
+
+    // The charts are fetched alongside a single price lookup for every holding
+    let handles: Vec<_> = ids
+        .iter()
+        .cloned()
+        .map(|coin_id| {
+            tokio::spawn(async move {
+                get_provider()
+                    .get_market_chart(&coin_id, timeframe, currency)
+                    .await
+            })
+        })
+        .collect();
+    let prices = get_provider()
+        .get_simple_prices(&ids, slice::from_ref(&portfolio.currency))
+        .await?;
+    // A coin whose chart can't be fetched is left out of the history rather than failing the view
+    let mut charts = vec![];
+    for handle in handles {
+        charts.push(handle.await.ok().and_then(|chart| chart.ok()));
+    }
+
+    let coins = index::get_snapshot();
+    let mut positions: Vec<Position> = holdings
+        .into_iter()
+        .map(|holding| Position {
+            symbol: coins
+                .iter()
+                .find(|coin| coin.id == holding.coin)
+                .map(|coin| coin.symbol.to_uppercase())
+                .unwrap_or_else(|| holding.coin.clone()),
+            price: prices
+                .get(&holding.coin)
+                .and_then(|coin_prices| coin_prices.get(&portfolio.currency))
+                .copied(),
+            holding,
+        })
+        .collect();
+
+    let values = get_value_history(&positions, &charts);
+    let missing: Vec<String> = positions
+        .iter()
+        .zip(charts.iter())
+        .filter(|(_, chart)| chart.is_none())
+        .map(|(position, _)| position.symbol.clone())
+        .collect();
+    positions.sort_by_key(|position| Reverse(position.value()));
+
+    // Holdings without a price are left out of the totals, so their cost doesn't count as a loss
+    let priced: Vec<&Position> = positions.iter().filter(|p| p.price.is_some()).collect();
+    let total_value: Decimal = priced.iter().filter_map(|p| p.value()).sum();
+    let total_cost: Decimal = priced.iter().map(|p| p.holding.cost).sum();
+
+    let allocation: Vec<(String, f64)> = priced
+        .iter()
+        .map(|p| {
+            let value = p
+                .value()
+                .and_then(|value| value.to_f64())
+                .unwrap_or_default();
+            (p.symbol.clone(), value)
+        })
+        .collect();
+
+    let theme = get_guild_settings(command.guild_id.map(|id| id.0))
+        .await
+        .get_theme(None);
+    let name = format!("portfolio_{}", user_id);
+    let sheet = match get_export_option(command)? {
+        Some(_) => Some(get_series_sheet(
+            &name,
+            &[("value".into(), values.clone())],
+        )?),
+        None => None,
+    };
+    let chart = tokio::task::spawn_blocking(move || {
+        get_portfolio_chart(&allocation, &values, &name, &currency, &theme)
+    })
+    .await??;
+
+    let mut fields: Vec<(String, String, bool)> = vec![
+        (
+            "Value".into(),
+            format!(
+                "```{}```",
+                Money::from_decimal(total_value.round_dp(2), &currency)
+            ),
+            true,
+        ),
+        (
+            "Cost".into(),
+            format!(
+                "```{}```",
+                Money::from_decimal(total_cost.round_dp(2), &currency)
+            ),
+            true,
+        ),
+        (
+            "Unrealized P&L".into(),
+            format_profit(total_value - total_cost, total_cost, &currency),
+            true,
+        ),
+    ];
+    fields.extend(
+        positions
+            .iter()
+            .take(MAX_HOLDING_FIELDS)
+            .map(|position| get_position_field(position, total_value, &currency)),
+    );
+
+    let mut description = match positions.len() > MAX_HOLDING_FIELDS {
+        true => format!(
+            "Value over the {} timeframe, with {} smaller holdings not listed",
+            timeframe.label(),
+            positions.len() - MAX_HOLDING_FIELDS
+        ),
+        false => format!("Value over the {} timeframe", timeframe.label()),
+    };
+    if !missing.is_empty() {
+        description.push_str(&format!(
+            "\nLeft out of the chart for lack of price history: {}",
+            missing.join(", ")
+        ));
+    }
+    let attachment = format!("attachment://{}", chart.filename);
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .author(|a| {
+            a.icon_url(command.user.face())
+                .name(format!("{}'s portfolio", command.user.name))
+        })
+        .description(description)
+        .fields(fields)
+        .color(Colour::DARK_GOLD)
+        .timestamp(Timestamp::now())
+        .image(attachment);
+
+    let mut attachments: Vec<Attachment> = vec![chart.into()];
+    attachments.extend(sheet.map(Attachment::from));
+
+    send_embed(
+        ctx,
+        command,
+        embed,
+        CreateComponents::default(),
+        attachments,
+    )
+    .await
+}
+
+/// What the current holdings were worth over the charts' timeframe. Coins without a chart are
+/// dropped, and of the rest only times every coin has a price for are kept.
+fn get_value_history(positions: &[Position], charts: &[Option<MarketChart>]) -> Vec<(f64, f64)> {
+    let (charts, amounts): (Vec<&MarketChart>, Vec<f64>) = charts
+        .iter()
+        .zip(positions.iter())
+        .filter_map(|(chart, p)| {
+            Some((
+                chart.as_ref()?,
+                p.holding.amount.to_f64().unwrap_or_default(),
+            ))
+        })
+        .unzip();
+    let times = match charts.iter().max_by_key(|chart| chart.prices.len()) {
+        Some(chart) => chart.prices.iter().map(|(time, _)| *time),
+        None => return vec![],
+    };
+
+    times
+        .filter_map(|time| {
+            let value = charts
+                .iter()
+                .zip(amounts.iter())
+                .map(|(chart, amount)| Some(chart.price_at(time)? * amount))
+                .sum::<Option<f64>>()?;
+            Some((time, value))
+        })
+        .collect()
+}
+
+fn get_position_field(
+    position: &Position,
+    total_value: Decimal,
+    currency: &Currency,
+) -> (String, String, bool) {
+    let holding = &position.holding;
+    let amount = format!(
+        "{} @ {}",
+        holding.amount.normalize(),
+        Money::from_decimal(holding.average_price().round_dp(8), currency)
+    );
+
+    let summary = match position.value() {
+        Some(value) => {
+            let share = match total_value.is_zero() {
+                true => Decimal::ZERO,
+                false => value / total_value * Decimal::ONE_HUNDRED,
+            };
+            format!(
+                "{}\n```{}```{}{:.1}% of the portfolio",
+                amount,
+                Money::from_decimal(value.round_dp(2), currency),
+                format_profit(value - holding.cost, holding.cost, currency),
+                share
+            )
+        }
+        None => format!("{}\nNo price available", amount),
+    };
+
+    (position.symbol.clone(), summary, true)
+}
+
+/// A coin's lots and profit for the report.
+fn get_book_summary(book: &Book, price: Option<Decimal>, currency: &Currency) -> String {
+    let held = match book.lots.len() {
+        0 => "None held".to_string(),
+        1 => format!("{} held in 1 lot", book.amount().normalize()),
+        lots => format!("{} held in {} lots", book.amount().normalize(), lots),
+    };
+    let unrealized = match price {
+        Some(price) => format!(
+            "Unrealized{}",
+            format_profit(book.unrealized(price), book.cost(), currency)
+        ),
+        None => "No price available".into(),
+    };
+
+    format!(
+        "{}\nRealized{}{}",
+        held,
+        format_profit(book.realized, Decimal::ZERO, currency),
+        unrealized
+    )
+}
+
+/// Profit or loss as a diff block, eg. `+$120.00 (+6.0%)`. The percentage is left out
+/// without a cost to compare to.
+fn format_profit(profit: Decimal, cost: Decimal, currency: &Currency) -> String {
+    let prefix = if profit.is_sign_negative() { "" } else { "+" };
+    let percent = match cost.is_zero() {
+        true => "".into(),
+        false => format!(" ({}{:.1}%)", prefix, profit / cost * Decimal::ONE_HUNDRED),
+    };
+
+    format!(
+        "```diff\n{}{}{}```",
+        prefix,
+        Money::from_decimal(profit.round_dp(2), currency),
+        percent
+    )
+}
+
+/// One line of the transaction list, eg. `#3 • 1 Jun 2022 • Buy 2.5 ethereum at $1,800.00`.
+fn describe(tx: &Transaction, currency: &Currency) -> String {
+    let date = NaiveDateTime::from_timestamp(tx.time, 0).format("%e %b %Y");
+    let price = match tx.kind {
+        TransactionKind::TransferOut => "".into(),
+        _ => format!(" at {}", Money::from_decimal(tx.price, currency)),
+    };
+
+    format!(
+        "#{} • {} • {} {} {}{}",
+        tx.id,
+        date.to_string().trim(),
+        tx.kind.label(),
+        tx.amount.normalize(),
+        tx.coin,
+        price
+    )
+}
+
+async fn get_current_price(coin_id: &str, currency_code: &str) -> Result<Decimal> {
+    let (coin_id, currency_code) = (coin_id.to_string(), currency_code.to_string());
+    let prices = get_provider()
+        .get_simple_prices(slice::from_ref(&coin_id), slice::from_ref(&currency_code))
+        .await?;
+
+    prices
+        .get(&coin_id)
+        .and_then(|coin_prices| coin_prices.get(&currency_code))
+        .copied()
+        .ok_or_else(|| anyhow!("No price available for {}, give one instead", coin_id))
+}
+
+/// The transaction's date as a unix timestamp. Today, or no date at all, means right now so
+/// the transaction lands after everything recorded earlier today.
+fn get_time_arg(command_info: &CommandInfo) -> Result<i64> {
+    let date = match command_info.get_arg("date") {
+        Some(date) => date.as_str().unwrap_or_default().trim().to_string(),
+        None => return Ok(Utc::now().timestamp()),
+    };
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|_| anyhow!("Dates look like 2022-06-01, got `{}`", date))?;
+    let today = Utc::today().naive_utc();
+
+    match date.cmp(&today) {
+        Ordering::Less => Ok(date.and_hms(0, 0, 0).timestamp()),
+        Ordering::Equal => Ok(Utc::now().timestamp()),
+        Ordering::Greater => Err(anyhow!("That date hasn't happened yet")),
+    }
+}
+
+fn get_columns_arg(command_info: &CommandInfo) -> Columns {
+    let column = |name: &str, default: String| {
+        command_info
+            .get_arg(name)
+            .and_then(|value| value.as_str().map(|header| header.trim().to_string()))
+            .filter(|header| !header.is_empty())
+            .unwrap_or(default)
+    };
+    let defaults = Columns::default();
+
+    Columns {
+        date: column("date_column", defaults.date),
+        kind: column("type_column", defaults.kind),
+        coin: column("coin_column", defaults.coin),
+        amount: column("amount_column", defaults.amount),
+        price: column("price_column", defaults.price),
+        currency: column("currency_column", defaults.currency),
+    }
+}
+
+/// The currency a file's prices are in. A file that names it, like an export, is taken at
+/// its word and has to be in a single currency, which the `currency` option can't override.
+async fn get_import_currency(
+    command: &ApplicationCommandInteraction,
+    command_info: &CommandInfo<'_>,
+    user_id: u64,
+    rows: &[Row],
+) -> Result<String> {
+    let option = get_record_currency(command, command_info, user_id)
+        .await?
+        .iso_alpha_code
+        .to_lowercase();
+    let named = match rows.iter().find_map(|row| row.currency.clone()) {
+        Some(named) => named,
+        None => return Ok(option),
+    };
+
+    if let Some(row) = rows.iter().find(|row| {
+        row.currency
+            .as_ref()
+            .is_some_and(|currency| *currency != named)
+    }) {
+        return Err(anyhow!(
+            "Line {}: prices have to be in one currency, but this one is in {} rather than {}",
+            row.line,
+            row.currency.as_deref().unwrap_or_default().to_uppercase(),
+            named.to_uppercase()
+        ));
+    }
+    if command_info.get_arg("currency").is_some() && option != named {
+        return Err(anyhow!(
+            "The file's prices are in {}, not {}",
+            named.to_uppercase(),
+            option.to_uppercase()
+        ));
+    }
+
+    Ok(named)
+}
+
+/// The currency transactions are recorded in. Leaving it out keeps to the portfolio's own.
+async fn get_record_currency(
+    command: &ApplicationCommandInteraction,
+    command_info: &CommandInfo<'_>,
+    user_id: u64,
+) -> Result<Currency> {
+    match command_info.get_arg("currency") {
+        Some(_) => get_currency_option(command),
+        None => Ok(get_currency(&get_portfolio(user_id).await)),
+    }
+}
+
+fn get_currency(portfolio: &Portfolio) -> Currency {
+    *iso::find(&portfolio.currency.to_uppercase()).unwrap_or(iso::USD)
+}
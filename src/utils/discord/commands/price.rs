@@ -0,0 +1,11 @@
+use anyhow::Result;
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use super::niche;
+
+/// /price is /niche under another name, for `COMMAND_MODE=price`.
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    niche::respond(ctx, command, "coin").await
+}
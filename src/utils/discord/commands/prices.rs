@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use serenity::builder::CreateEmbed;
+use serenity::model::Timestamp;
+use serenity::utils::Colour;
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::discord::utils::{
+    get_coins_arg, get_command_info, get_currency_option, send_text_embed,
+};
+use crate::utils::gecko::get_provider;
+use crate::utils::gecko::lib::{CoinInfo, MarketChange};
+use crate::utils::gecko::resolver::{resolve_coin, Resolution};
+
+// Three fields to a row, so the embed stays compact
+const MAX_COINS: usize = 12;
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+    let queries = get_coins_arg(&command_info);
+
+    if queries.is_empty() {
+        return Err(anyhow!("Which coins? eg. `btc,eth,sol`"));
+    }
+    if queries.len() > MAX_COINS {
+        return Err(anyhow!("Quote at most {} coins at a time", MAX_COINS));
+    }
+
+    // One coin the bot can't tell apart from others shouldn't keep the rest from being quoted
+    let mut coin_ids: Vec<String> = vec![];
+    let mut ambiguous: Vec<(String, Vec<CoinInfo>)> = vec![];
+    let mut not_found: Vec<String> = vec![];
+    for query in queries {
+        let coin_id = match resolve_coin(get_provider(), &query).await? {
+            Resolution::Found(info) => info.id,
+            Resolution::Ambiguous(candidates) => match get_symbol_match(&query, &candidates) {
+                Some(info) => info.id.clone(),
+                None => {
+                    ambiguous.push((query, candidates));
+                    continue;
+                }
+            },
+            Resolution::NotFound => {
+                not_found.push(query);
+                continue;
+            }
+        };
+        if !coin_ids.contains(&coin_id) {
+            coin_ids.push(coin_id);
+        }
+    }
+
+    // Every coin comes back from a single request
+    let currency = get_currency_option(&command)?;
+    let markets = match coin_ids.is_empty() {
+        true => vec![],
+        false => get_provider().get_markets(&coin_ids, currency).await?,
+    };
+
+    let mut fields: Vec<(String, String, bool)> = coin_ids
+        .iter()
+        .filter_map(|coin_id| markets.iter().find(|market| &market.id == coin_id))
+        .map(|market| {
+            let changes = market.get_formatted_changes(&[
+                MarketChange::PercentageChange1h,
+                MarketChange::PercentageChange24h,
+                MarketChange::PercentageChange7d,
+            ]);
+
+            (
+                format!("{} ({})", market.name, market.symbol.to_uppercase()),
+                format!("{}{}", market.get_formatted_price(currency), changes),
+                true,
+            )
+        })
+        .collect();
+    if fields.is_empty() && ambiguous.is_empty() {
+        return match not_found.is_empty() {
+            true => Err(anyhow!("No prices available for those coins right now")),
+            false => Err(anyhow!("Couldn't find {}", not_found.join(", "))),
+        };
+    }
+
+    fields.extend(ambiguous.iter().map(|(query, candidates)| {
+        let suggestions = candidates
+            .iter()
+            .map(|coin| format!("{} → `{}`", coin.name, coin.id))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        (format!("Which {}?", query), suggestions, false)
+    }));
+
+    let mut description = format!("In {}", currency.iso_alpha_code);
+    if !not_found.is_empty() {
+        description = format!("{}. Couldn't find {}", description, not_found.join(", "));
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("Prices")
+        .description(description)
+        .fields(fields)
+        .color(Colour::DARK_GOLD)
+        .timestamp(Timestamp::now());
+
+    send_text_embed(&ctx, &command, embed).await
+}
+
+/// Several coins often share a symbol, eg. `eth`. The candidates come ranked by market cap,
+/// so the biggest one that has the symbol is taken to be the one meant.
+fn get_symbol_match<'a>(query: &str, candidates: &'a [CoinInfo]) -> Option<&'a CoinInfo> {
+    candidates.first().filter(|coin| {
+        coin.symbol.eq_ignore_ascii_case(query.trim()) && coin.market_cap_rank.is_some()
+    })
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::discord::utils::{get_command_info, send_content};
+use crate::utils::plotter::theme::Palette;
+use crate::utils::settings::{get_guild_settings, update_guild_settings, GuildSettings};
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+    let guild_id = command
+        .guild_id
+        .ok_or_else(|| anyhow!("Themes can only be set in a server"))?
+        .0;
+
+    let palette = match command_info.get_arg("palette") {
+        Some(name) => Some(
+            Palette::from_name(name.as_str().unwrap_or_default())
+                .ok_or_else(|| anyhow!("Unknown palette, pick one from the list"))?,
+        ),
+        None => None,
+    };
+    let accent = command_info.get_arg("accent").and_then(|a| a.as_bool());
+
+    // Without any options this just shows the current theme
+    let content = match (palette, accent) {
+        (None, None) => format!(
+            "__Chart theme__\n{}",
+            describe(&get_guild_settings(Some(guild_id)).await)
+        ),
+        _ => {
+            let settings = update_guild_settings(guild_id, |settings| {
+                if let Some(palette) = palette {
+                    settings.palette = palette;
+                }
+                if let Some(accent) = accent {
+                    settings.accent = accent;
+                }
+            })
+            .await?;
+
+            format!("🎨 Chart theme updated\n{}", describe(&settings))
+        }
+    };
+
+    send_content(&ctx, &command, content).await
+}
+
+fn describe(settings: &GuildSettings) -> String {
+    let accent = match settings.accent {
+        true => "on",
+        false => "off",
+    };
+
+    format!(
+        "• Palette: {}\n• Coin colour accents: {}",
+        settings.palette.label(),
+        accent
+    )
+}
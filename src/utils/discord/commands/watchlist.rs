@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::ToPrimitive;
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::model::Timestamp;
+use serenity::utils::Colour;
+use serenity::{
+    client::Context, model::interactions::application_command::ApplicationCommandInteraction,
+};
+
+use crate::utils::discord::lib::CommandInfo;
+use crate::utils::discord::utils::{
+    get_coins_arg, get_command_info, get_currency_option, resolve_coin_arg, send_content,
+    send_embed,
+};
+use crate::utils::gecko::get_provider;
+use crate::utils::plotter::{get_quote_table, QuoteRow};
+use crate::utils::settings::get_guild_settings;
+use crate::utils::watchlist::{add_coins, get_watchlist, remove_coins, Owner};
+
+pub async fn main(ctx: Context, command: ApplicationCommandInteraction) -> Result<()> {
+    let command_info = get_command_info(&command)?;
+    let owner = get_owner(&command, &command_info)?;
+
+    match command_info.get_subcommand().as_str() {
+        "show" => show(&ctx, &command, owner).await,
+        "add" | "remove" => {
+            if let Owner::Guild(_) = owner {
+                check_manage_guild(&command)?;
+            }
+
+            let mut coin_ids: Vec<String> = vec![];
+            for query in get_coins_arg(&command_info) {
+                match resolve_coin_arg(&ctx, &command, &query).await? {
+                    Some(coin_id) => coin_ids.push(coin_id),
+                    None => return Ok(()),
+                }
+            }
+            if coin_ids.is_empty() {
+                return Err(anyhow!("Which coins? eg. `bitcoin ethereum solana`"));
+            }
+
+            let content = match command_info.get_subcommand().as_str() {
+                "add" => match add_coins(owner, &coin_ids).await?.as_slice() {
+                    [] => "Those coins are already on the watchlist".to_string(),
+                    added => format!("👀 Added {} to the watchlist", added.join(", ")),
+                },
+                _ => match remove_coins(owner, &coin_ids).await?.as_slice() {
+                    [] => "None of those coins are on the watchlist".to_string(),
+                    removed => format!("🗑️ Removed {} from the watchlist", removed.join(", ")),
+                },
+            };
+            send_content(&ctx, &command, content).await
+        }
+        other => Err(anyhow!("Unknown watchlist command: {}", other)),
+    }
+}
+
+/// Replies with every coin on the watchlist in a single table, fetched in one go.
+async fn show(ctx: &Context, command: &ApplicationCommandInteraction, owner: Owner) -> Result<()> {
+    let coin_ids = get_watchlist(owner).await;
+    if coin_ids.is_empty() {
+        let content = "The watchlist is empty. Add to it with `/watchlist add`".into();
+        return send_content(ctx, command, content).await;
+    }
+
+    let currency = get_currency_option(command)?;
+    let markets = get_provider().get_markets(&coin_ids, currency).await?;
+
+    // Rows keep the watchlist's order, coins without a price are left out
+    let rows: Vec<QuoteRow> = coin_ids
+        .iter()
+        .filter_map(|coin_id| markets.iter().find(|market| &market.id == coin_id))
+        .filter_map(|market| {
+            Some(QuoteRow {
+                symbol: market.symbol.to_uppercase(),
+                name: market.name.clone(),
+                price: market.current_price?.to_f64()?,
+                change: market
+                    .price_change_percentage_24h_in_currency
+                    .and_then(|change| change.to_f64()),
+                sparkline: market
+                    .sparkline_in_7d
+                    .as_ref()
+                    .map(|sparkline| sparkline.price.clone())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+    if rows.is_empty() {
+        return Err(anyhow!("No prices available for the watchlist right now"));
+    }
+
+    let theme = get_guild_settings(command.guild_id.map(|id| id.0))
+        .await
+        .get_theme(None);
+    let name = match owner {
+        Owner::User(id) => format!("watchlist_{}", id),
+        Owner::Guild(id) => format!("watchlist_guild_{}", id),
+    };
+    let chart =
+        tokio::task::spawn_blocking(move || get_quote_table(&rows, &name, &currency, &theme))
+            .await??;
+
+    let title = match owner {
+        Owner::User(_) => format!("{}'s watchlist", command.user.name),
+        Owner::Guild(_) => "Server watchlist".into(),
+    };
+    let attachment = format!("attachment://{}", chart.filename);
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .author(|a| a.icon_url(command.user.face()).name(title))
+        .description(format!(
+            "Prices in {}, with the 24h change and the last 7 days",
+            currency.iso_alpha_code
+        ))
+        .color(Colour::DARK_GOLD)
+        .timestamp(Timestamp::now())
+        .image(attachment);
+
+    send_embed(
+        ctx,
+        command,
+        embed,
+        CreateComponents::default(),
+        vec![chart.into()],
+    )
+    .await
+}
+
+/// The server's shared watchlist when asked for, the user's own otherwise.
+fn get_owner(command: &ApplicationCommandInteraction, command_info: &CommandInfo) -> Result<Owner> {
+    let server = command_info
+        .get_arg("server")
+        .and_then(|server| server.as_bool())
+        .unwrap_or_default();
+
+    match (server, command.guild_id) {
+        (false, _) => Ok(Owner::User(command.user.id.0)),
+        (true, Some(guild_id)) => Ok(Owner::Guild(guild_id.0)),
+        (true, None) => Err(anyhow!("Server watchlists only exist in a server")),
+    }
+}
+
+fn check_manage_guild(command: &ApplicationCommandInteraction) -> Result<()> {
+    let allowed = command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild());
+
+    match allowed {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Only members who can manage the server can change its watchlist"
+        )),
+    }
+}
@@ -0,0 +1,321 @@
+use rusty_money::iso::{self, Currency};
+use serenity::builder::CreateComponents;
+use serenity::model::interactions::message_component::ButtonStyle;
+
+use super::TOP_CURRENCIES;
+use crate::utils::export::Export;
+use crate::utils::gecko::lib::Timeframe;
+use crate::utils::plotter::indicators::Indicator;
+
+const VIEW_PREFIX: &str = "v";
+// Discord rejects longer component ids
+const MAX_CUSTOM_ID_LENGTH: usize = 100;
+// An action row fits at most 5 buttons
+const TIMEFRAME_BUTTONS: [Timeframe; 5] = [
+    Timeframe::Day,
+    Timeframe::Week,
+    Timeframe::Month,
+    Timeframe::Year,
+    Timeframe::Max,
+];
+
+/// Which control of the price message was used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Timeframe,
+    Graph,
+    Currency,
+}
+
+impl Control {
+    fn key(&self) -> &'static str {
+        match self {
+            Control::Timeframe => "t",
+            Control::Graph => "g",
+            Control::Currency => "c",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "t" => Some(Control::Timeframe),
+            "g" => Some(Control::Graph),
+            "c" => Some(Control::Currency),
+            _ => None,
+        }
+    }
+}
+
+/// Everything needed to render a price message.
+///
+/// Each control's `custom_id` carries the view it switches to, so clicks keep
+/// working after a restart without any stored state.
+#[derive(Debug, Clone)]
+pub struct View {
+    pub coin: String,
+    pub graph: String,
+    pub timeframe: Timeframe,
+    pub currency: Currency,
+    pub indicators: Vec<Indicator>,
+    /// Data attached as CSV next to the chart
+    pub export: Option<Export>,
+}
+
+impl View {
+    /// The view packed into a `custom_id`, eg. `v:t:l:30:USD:s20,r14:c:bitcoin`. Every part
+    /// but the coin gets a short code, and `None` is returned if it still doesn't fit.
+    pub fn to_custom_id(&self, control: Control) -> Option<String> {
+        // The coin goes last as it is the only free-form part
+        let indicators = self
+            .indicators
+            .iter()
+            .map(get_indicator_code)
+            .collect::<Vec<String>>()
+            .join(",");
+        let export = self.export.map(get_export_code).unwrap_or_default();
+
+        let custom_id = format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}",
+            VIEW_PREFIX,
+            control.key(),
+            get_graph_code(&self.graph),
+            self.timeframe.days(),
+            self.currency.iso_alpha_code,
+            indicators,
+            export,
+            self.coin
+        );
+
+        match custom_id.len() <= MAX_CUSTOM_ID_LENGTH {
+            true => Some(custom_id),
+            false => None,
+        }
+    }
+
+    pub fn from_custom_id(custom_id: &str) -> Option<(Control, View)> {
+        let mut parts = custom_id.splitn(8, ':');
+
+        if parts.next()? != VIEW_PREFIX {
+            return None;
+        }
+
+        let control = Control::from_key(parts.next()?)?;
+        let graph = get_graph_from_code(parts.next()?)?.to_string();
+        let timeframe = Timeframe::from_days(parts.next()?)?;
+        let currency = *iso::find(parts.next()?)?;
+        let indicators = parts
+            .next()?
+            .split(',')
+            .filter(|code| !code.is_empty())
+            .map(get_indicator_name)
+            .collect::<Option<Vec<String>>>()?;
+        let indicators = Indicator::parse_list(&indicators.join(",")).ok()?;
+        let export = match parts.next()? {
+            "" => None,
+            code => Some(get_export_from_code(code)?),
+        };
+        let coin = parts.next()?.to_string();
+
+        Some((
+            control,
+            View {
+                coin,
+                graph,
+                timeframe,
+                currency,
+                indicators,
+                export,
+            },
+        ))
+    }
+}
+
+fn get_graph_code(graph: &str) -> &'static str {
+    match graph {
+        "ohlc" => "o",
+        _ => "l",
+    }
+}
+
+fn get_graph_from_code(code: &str) -> Option<&'static str> {
+    match code {
+        "l" => Some("line"),
+        "o" => Some("ohlc"),
+        _ => None,
+    }
+}
+
+fn get_export_code(export: Export) -> &'static str {
+    match export {
+        Export::Chart => "c",
+        Export::Sparkline => "s",
+    }
+}
+
+fn get_export_from_code(code: &str) -> Option<Export> {
+    match code {
+        "c" => Some(Export::Chart),
+        "s" => Some(Export::Sparkline),
+        _ => None,
+    }
+}
+
+/// The indicator's name shortened to its first letter, eg. `s20` for `sma20`.
+fn get_indicator_code(indicator: &Indicator) -> String {
+    let name = indicator.name();
+    let period = name.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+
+    format!("{}{}", &name[..1], period)
+}
+
+fn get_indicator_name(code: &str) -> Option<String> {
+    let (kind, period) = code.split_at(code.len().min(1));
+    let kind = match kind {
+        "s" => "sma",
+        "e" => "ema",
+        "b" => "bbands",
+        "r" => "rsi",
+        "m" => "macd",
+        _ => return None,
+    };
+
+    Some(format!("{}{}", kind, period))
+}
+
+/// Timeframe and graph buttons plus a currency select for the price message. A coin whose
+/// id is too long to fit in them gets none.
+pub fn build_components(view: &View) -> CreateComponents {
+    let mut components = CreateComponents::default();
+
+    let timeframe_buttons: Option<Vec<(Timeframe, String)>> = TIMEFRAME_BUTTONS
+        .iter()
+        .map(|timeframe| {
+            let target = View {
+                timeframe: *timeframe,
+                ..view.clone()
+            };
+            Some((*timeframe, target.to_custom_id(Control::Timeframe)?))
+        })
+        .collect();
+    let graph_buttons: Option<Vec<(&str, &str, String)>> = [("line", "Line"), ("ohlc", "OHLC")]
+        .iter()
+        .map(|(graph, label)| {
+            let target = View {
+                graph: graph.to_string(),
+                ..view.clone()
+            };
+            Some((*graph, *label, target.to_custom_id(Control::Graph)?))
+        })
+        .collect();
+    let currency_select = view.to_custom_id(Control::Currency);
+
+    let (timeframe_buttons, graph_buttons, currency_select) =
+        match (timeframe_buttons, graph_buttons, currency_select) {
+            (Some(timeframes), Some(graphs), Some(currency)) => (timeframes, graphs, currency),
+            _ => return components,
+        };
+
+    components
+        .create_action_row(|row| {
+            timeframe_buttons.iter().for_each(|(timeframe, custom_id)| {
+                let selected = *timeframe == view.timeframe;
+
+                row.create_button(|button| {
+                    button
+                        .custom_id(custom_id)
+                        .label(timeframe.label())
+                        .style(get_button_style(selected))
+                        .disabled(selected)
+                });
+            });
+            row
+        })
+        .create_action_row(|row| {
+            graph_buttons.iter().for_each(|(graph, label, custom_id)| {
+                let selected = *graph == view.graph;
+
+                row.create_button(|button| {
+                    button
+                        .custom_id(custom_id)
+                        .label(label)
+                        .style(get_button_style(selected))
+                        .disabled(selected)
+                });
+            });
+            row
+        })
+        .create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(currency_select)
+                    .placeholder("Currency")
+                    .options(|options| {
+                        TOP_CURRENCIES.iter().for_each(|currency| {
+                            options.create_option(|option| {
+                                option
+                                    .label(currency.name)
+                                    .value(currency.iso_alpha_code)
+                                    .default_selection(
+                                        currency.iso_alpha_code == view.currency.iso_alpha_code,
+                                    )
+                            });
+                        });
+                        options
+                    })
+            })
+        });
+
+    components
+}
+
+fn get_button_style(selected: bool) -> ButtonStyle {
+    if selected {
+        ButtonStyle::Primary
+    } else {
+        ButtonStyle::Secondary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(coin: &str) -> View {
+        View {
+            coin: coin.into(),
+            graph: "ohlc".into(),
+            timeframe: Timeframe::Max,
+            currency: *iso::EUR,
+            indicators: vec![
+                Indicator::Sma(200),
+                Indicator::Ema(150),
+                Indicator::BollingerBands,
+                Indicator::Rsi(100),
+                Indicator::Macd,
+            ],
+            export: Some(Export::Sparkline),
+        }
+    }
+
+    #[test]
+    fn custom_id_round_trips() {
+        let view = view("wrapped-bitcoin");
+        let custom_id = view.to_custom_id(Control::Graph).unwrap();
+        let (control, parsed) = View::from_custom_id(&custom_id).unwrap();
+
+        assert_eq!(control, Control::Graph);
+        assert_eq!(parsed.coin, view.coin);
+        assert_eq!(parsed.graph, view.graph);
+        assert_eq!(parsed.timeframe, view.timeframe);
+        assert_eq!(parsed.currency, view.currency);
+        assert_eq!(parsed.indicators, view.indicators);
+        assert_eq!(parsed.export, view.export);
+    }
+
+    #[test]
+    fn long_coin_ids_get_no_components() {
+        let view = view(&"a".repeat(MAX_CUSTOM_ID_LENGTH));
+
+        assert!(view.to_custom_id(Control::Timeframe).is_none());
+        assert!(build_components(&view).0.is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+use std::env;
+
+use serenity::model::id::GuildId;
+
+// Discord allows 100 global commands; /niche and the 7 feature commands take 8 of them
+const MAX_COIN_COUNT: u8 = 92;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandMode {
+    /// One command per top coin (eg. /bitcoin), plus /niche for everything else.
+    PerCoin,
+    /// A single /price command that autocompletes any coin.
+    Price,
+}
+
+lazy_static! {
+    static ref COMMAND_MODE: CommandMode = read_command_mode();
+    static ref COIN_COUNT: u8 = read_coin_count();
+    static ref DEV_GUILD: Option<GuildId> = read_dev_guild();
+}
+
+fn read_command_mode() -> CommandMode {
+    match env::var("COMMAND_MODE") {
+        Ok(mode) => match mode.to_lowercase().as_str() {
+            "coins" => CommandMode::PerCoin,
+            "price" => CommandMode::Price,
+            other => panic!("Unknown command mode: {}", other),
+        },
+        Err(_) => CommandMode::PerCoin,
+    }
+}
+
+fn read_coin_count() -> u8 {
+    match env::var("COIN_COUNT") {
+        Ok(count) => count
+            .parse::<u8>()
+            .expect("COIN_COUNT must be a number")
+            .min(MAX_COIN_COUNT),
+        Err(_) => MAX_COIN_COUNT,
+    }
+}
+
+fn read_dev_guild() -> Option<GuildId> {
+    match env::var("DEV_GUILD_ID") {
+        Ok(id) if !id.is_empty() => Some(GuildId(
+            id.parse::<u64>().expect("DEV_GUILD_ID must be a guild id"),
+        )),
+        _ => None,
+    }
+}
+
+pub fn get_command_mode() -> CommandMode {
+    *COMMAND_MODE
+}
+
+/// How many of the top coins get their own command in `CommandMode::PerCoin`.
+pub fn get_coin_count() -> u8 {
+    *COIN_COUNT
+}
+
+/// When set, commands are registered to this guild only, which applies instantly.
+pub fn get_dev_guild() -> Option<GuildId> {
+    *DEV_GUILD
+}
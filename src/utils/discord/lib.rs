@@ -1,3 +1,5 @@
+use crate::utils::export::Sheet;
+use crate::utils::plotter::Chart;
 use serenity::json::Value;
 
 pub struct Arg<'a> {
@@ -7,6 +9,8 @@ pub struct Arg<'a> {
 
 pub struct CommandInfo<'a> {
     pub name: &'a str,
+    /// The subcommand group and subcommand that were invoked, outermost first
+    pub subcommands: Vec<&'a str>,
     pub args: Vec<Arg<'a>>,
 }
 
@@ -17,4 +21,33 @@ impl CommandInfo<'_> {
             _ => None,
         }
     }
+
+    /// The invoked subcommand path, eg. "add" or "tx buy"
+    pub fn get_subcommand(&self) -> String {
+        self.subcommands.join(" ")
+    }
+}
+
+/// A file uploaded with a message, such as a chart or its data.
+pub struct Attachment {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+impl From<Chart> for Attachment {
+    fn from(chart: Chart) -> Self {
+        Attachment {
+            filename: chart.filename,
+            data: chart.data,
+        }
+    }
+}
+
+impl From<Sheet> for Attachment {
+    fn from(sheet: Sheet) -> Self {
+        Attachment {
+            filename: sheet.filename,
+            data: sheet.data,
+        }
+    }
 }
@@ -1,27 +1,39 @@
 mod command_handler;
 mod commands;
+mod components;
+pub mod config;
 pub mod lib;
+mod sync;
 mod utils;
 
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
-use command_handler::handle_command;
+use command_handler::{handle_autocomplete, handle_command, handle_component};
 use rusty_money::iso::{self, Currency};
-use serenity::builder::CreateApplicationCommandOption;
+use serenity::builder::{CreateApplicationCommand, CreateApplicationCommandOption};
 use serenity::model::guild::Guild;
-use serenity::model::interactions::application_command::{
-    ApplicationCommand, ApplicationCommandOptionType,
-};
+use serenity::model::interactions::application_command::ApplicationCommandOptionType;
+use serenity::model::Permissions;
 use serenity::{async_trait, model::gateway::Ready, model::interactions::Interaction, prelude::*};
 
-use crate::utils::gecko::get_top_coins;
+use crate::utils::alerts;
+use crate::utils::export::Export;
+use crate::utils::gecko::lib::{CoinInfo, Timeframe};
+use crate::utils::gecko::{get_provider, index};
+use crate::utils::plotter::theme::Palette;
+use crate::utils::portfolio::ledger::CostMethod;
 
-use self::utils::message_owner;
+use self::config::{get_coin_count, get_command_mode, get_dev_guild, CommandMode};
+use self::sync::{sync_commands, CommandScope};
+pub use self::utils::message_owner;
 
-const COIN_COUNT: u8 = 99;
+// Discord's limit on global (and per guild) slash commands
+const MAX_COMMANDS: usize = 100;
 
 lazy_static! {
+    static ref COMMANDS_SYNCED: AtomicBool = AtomicBool::new(false);
     static ref TOP_CURRENCIES: Vec<&'static Currency> = vec![
         iso::USD,
         iso::EUR,
@@ -60,9 +72,15 @@ impl EventHandler for Handler {
             Interaction::ApplicationCommand(command) => {
                 tokio::spawn(async move { handle_command(ctx, command).await })
             }
+            Interaction::Autocomplete(autocomplete) => {
+                tokio::spawn(async move { handle_autocomplete(ctx, autocomplete).await })
+            }
+            Interaction::MessageComponent(component) => {
+                tokio::spawn(async move { handle_component(ctx, component).await })
+            }
             _ => return,
         };
-        // Ignore any non-commands for now
+        // Ignore any other interactions for now
     }
 
     async fn guild_create(&self, ctx: Context, guild: Guild) {
@@ -92,22 +110,77 @@ impl EventHandler for Handler {
         };
         message_owner(&ctx, msg).await;
 
-        update_commands(&ctx).await;
+        index::spawn_refresh(get_provider(), ctx.clone());
+        alerts::spawn_poller(ctx.clone());
+        update_commands(&ctx, ready.application.id.0).await;
     }
 }
 
-async fn update_commands(ctx: &Context) {
+async fn update_commands(ctx: &Context, application_id: u64) {
     let update_commands = env::var("UPDATE_COMMANDS").unwrap_or("n".into());
 
-    if update_commands.as_str() != "y" {
+    // Every shard fires `ready`, including on reconnects, but one sync is enough
+    if update_commands.as_str() != "y" || COMMANDS_SYNCED.swap(true, Ordering::SeqCst) {
         return;
     }
 
     let start = Instant::now();
 
-    let coin_list = get_top_coins(COIN_COUNT).await.unwrap();
+    let feature_commands = get_feature_commands();
+    let mut commands = match get_command_mode() {
+        CommandMode::PerCoin => {
+            // Coin commands share Discord's budget with /niche and the feature commands
+            let budget = MAX_COMMANDS - 1 - feature_commands.len();
+            let coin_count = get_coin_count().min(budget as u8);
+
+            match get_provider().get_top_coins(coin_count).await {
+                Ok(coin_list) => get_coin_commands(coin_list),
+                Err(err) => return report_sync_error(ctx, err.into()).await,
+            }
+        }
+        CommandMode::Price => vec![get_price_command()],
+    };
+    commands.extend(feature_commands);
+
+    let scope = match get_dev_guild() {
+        Some(guild) => CommandScope::Guild(guild),
+        None => CommandScope::Global,
+    };
+
+    let summary = match sync_commands(&ctx.http, application_id, scope, commands).await {
+        Ok(summary) => summary,
+        Err(err) => return report_sync_error(ctx, err).await,
+    };
+
+    let elapsed = start.elapsed();
+    let mut msg = format!(
+        "__Command Sync Success__ ({:?}, {:.3?} elapsed)\n\n",
+        scope, elapsed
+    );
+
+    if summary.is_empty() {
+        msg = format!("{}• Already up to date\n", msg);
+    }
+    for (label, names) in [
+        ("created", &summary.created),
+        ("edited", &summary.edited),
+        ("deleted", &summary.deleted),
+    ] {
+        if !names.is_empty() {
+            msg = format!("{}• {} {}: {}\n", msg, names.len(), label, names.join(", "));
+        }
+    }
+
+    message_owner(ctx, msg).await;
+}
+
+/// Lets the owner know, and lets the next `ready` try again.
+async fn report_sync_error(ctx: &Context, err: anyhow::Error) {
+    COMMANDS_SYNCED.store(false, Ordering::SeqCst);
+    message_owner(ctx, format!("__Command Sync Failure__\n\n{:?}", err)).await;
+}
 
-    // Create the currencies option
+fn get_currency_option() -> CreateApplicationCommandOption {
     let mut currency_option = CreateApplicationCommandOption::default();
     currency_option.name("currency");
     currency_option.description("Preferred currency. Default is: USD");
@@ -117,7 +190,10 @@ async fn update_commands(ctx: &Context) {
         currency_option.add_string_choice(currency.name, currency.iso_alpha_code);
     });
 
-    // Create the graph type option
+    currency_option
+}
+
+fn get_graph_option() -> CreateApplicationCommandOption {
     let mut graph_option = CreateApplicationCommandOption::default();
     graph_option.name("graph");
     graph_option.description("Preferred graph type. Default is: line");
@@ -125,46 +201,536 @@ async fn update_commands(ctx: &Context) {
     graph_option.add_string_choice("Line Graph", "line");
     graph_option.add_string_choice("OHLC Graph", "ohlc");
 
-    ApplicationCommand::set_global_application_commands(&ctx.http, |command| {
-        // Coin commands
-        coin_list.into_iter().for_each(|coin| {
-            let currency_option1 = currency_option.clone();
-            let graph_option1 = graph_option.clone();
-            command.create_application_command(|cmd| {
-                cmd.name(coin.id)
-                    .description(format!(
-                        "Fetch price info for {} ({})",
-                        coin.name, coin.symbol
-                    ))
-                    .add_option(currency_option1)
-                    .add_option(graph_option1)
-            });
+    graph_option
+}
+
+fn get_timeframe_option() -> CreateApplicationCommandOption {
+    let mut timeframe_option = CreateApplicationCommandOption::default();
+    timeframe_option.name("timeframe");
+    timeframe_option.description("How far back the graph goes. Default is: 7d");
+    timeframe_option.kind(ApplicationCommandOptionType::String);
+
+    Timeframe::ALL.iter().for_each(|timeframe| {
+        timeframe_option.add_string_choice(timeframe.name(), timeframe.name());
+    });
+
+    timeframe_option
+}
+
+fn get_indicators_option() -> CreateApplicationCommandOption {
+    let mut indicators_option = CreateApplicationCommandOption::default();
+    indicators_option.name("indicators");
+    indicators_option.description("Comma separated indicators, eg. sma20,ema50,bbands,rsi,macd");
+    indicators_option.kind(ApplicationCommandOptionType::String);
+
+    indicators_option
+}
+
+/// Offers the data behind a chart as a CSV file. Only coins have a sparkline to offer.
+fn get_export_option(with_sparkline: bool) -> CreateApplicationCommandOption {
+    let mut export_option = CreateApplicationCommandOption::default();
+    export_option.name("export");
+    export_option.description("Also attach data as a CSV file");
+    export_option.kind(ApplicationCommandOptionType::String);
+
+    Export::ALL
+        .iter()
+        .filter(|export| with_sparkline || **export != Export::Sparkline)
+        .for_each(|export| {
+            export_option.add_string_choice(export.label(), export.name());
         });
 
-        // Custom commands
-        command.create_application_command(|cmd| {
-            cmd.name("niche")
-                .description("Fetch price info for a (more niche) coin")
-                .create_option(|option| {
+    export_option
+}
+
+fn get_coin_commands(coin_list: Vec<CoinInfo>) -> Vec<CreateApplicationCommand> {
+    // Coin commands
+    let mut commands: Vec<CreateApplicationCommand> = coin_list
+        .into_iter()
+        .map(|coin| {
+            let mut cmd = CreateApplicationCommand::default();
+            cmd.name(coin.id)
+                .description(format!(
+                    "Fetch price info for {} ({})",
+                    coin.name, coin.symbol
+                ))
+                .add_option(get_currency_option())
+                .add_option(get_graph_option())
+                .add_option(get_timeframe_option())
+                .add_option(get_indicators_option())
+                .add_option(get_export_option(true));
+            cmd
+        })
+        .collect();
+
+    // Custom commands
+    let mut niche = CreateApplicationCommand::default();
+    niche
+        .name("niche")
+        .description("Fetch price info for a (more niche) coin")
+        .create_option(|option| {
+            option
+                .name("coin")
+                .description("The coin's name")
+                .kind(ApplicationCommandOptionType::String)
+                .required(true)
+                .set_autocomplete(true)
+        })
+        .add_option(get_currency_option())
+        .add_option(get_timeframe_option())
+        .add_option(get_indicators_option())
+        .add_option(get_export_option(true));
+    // .add_option(get_graph_option())
+    commands.push(niche);
+
+    commands
+}
+
+fn get_price_command() -> CreateApplicationCommand {
+    let mut price = CreateApplicationCommand::default();
+    price
+        .name("price")
+        .description("Fetch price info for any coin")
+        .create_option(|option| {
+            option
+                .name("coin")
+                .description("The coin's name, symbol or id")
+                .kind(ApplicationCommandOptionType::String)
+                .required(true)
+                .set_autocomplete(true)
+        })
+        .add_option(get_currency_option())
+        .add_option(get_graph_option())
+        .add_option(get_timeframe_option())
+        .add_option(get_indicators_option())
+        .add_option(get_export_option(true));
+
+    price
+}
+
+/// Commands registered in every mode, alongside the price commands.
+fn get_feature_commands() -> Vec<CreateApplicationCommand> {
+    vec![
+        get_alert_command(),
+        get_compare_command(),
+        get_pair_command(),
+        get_portfolio_command(),
+        get_prices_command(),
+        get_theme_command(),
+        get_watchlist_command(),
+    ]
+}
+
+fn get_alert_command() -> CreateApplicationCommand {
+    let mut alert = CreateApplicationCommand::default();
+    alert
+        .name("alert")
+        .description("Get notified when a coin crosses a price")
+        .create_option(|add| {
+            add.name("add")
+                .description("Add a price alert")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .create_sub_option(|option| {
                     option
                         .name("coin")
-                        .description("The coin's name")
+                        .description("The coin's name, symbol or id")
                         .kind(ApplicationCommandOptionType::String)
                         .required(true)
+                        .set_autocomplete(true)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("above")
+                        .description("Notify when the price rises above this")
+                        .kind(ApplicationCommandOptionType::Number)
+                        .min_number_value(0.0)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("below")
+                        .description("Notify when the price falls below this")
+                        .kind(ApplicationCommandOptionType::Number)
+                        .min_number_value(0.0)
+                })
+                .add_sub_option(get_currency_option())
+                .create_sub_option(|option| {
+                    option
+                        .name("notify")
+                        .description("Where to notify you. Default is: DM")
+                        .kind(ApplicationCommandOptionType::String)
+                        .add_string_choice("Direct message", "dm")
+                        .add_string_choice("This channel", "channel")
                 })
-                .add_option(currency_option)
-            // .add_option(graph_option)
         })
-    })
-    .await
-    .expect("Error creating global commands");
+        .create_option(|list| {
+            list.name("list")
+                .description("List your price alerts")
+                .kind(ApplicationCommandOptionType::SubCommand)
+        })
+        .create_option(|remove| {
+            remove
+                .name("remove")
+                .description("Remove a price alert")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("id")
+                        .description("The alert's number, see /alert list")
+                        .kind(ApplicationCommandOptionType::Integer)
+                        .required(true)
+                })
+        });
 
-    let elapsed = start.elapsed();
-    message_owner(
-        &ctx,
-        format!("Global Command Updates Success! ({:.3?} elapsed)", elapsed),
-    )
-    .await;
+    alert
+}
+
+fn get_compare_command() -> CreateApplicationCommand {
+    let mut compare = CreateApplicationCommand::default();
+    compare
+        .name("compare")
+        .description("Compare how several coins performed")
+        .create_option(|option| {
+            option
+                .name("coins")
+                .description("Coins separated by commas or spaces, eg. solana,ethereum,bitcoin")
+                .kind(ApplicationCommandOptionType::String)
+                .required(true)
+        })
+        .add_option(get_timeframe_option())
+        .add_option(get_currency_option())
+        .add_option(get_export_option(true));
+
+    compare
+}
+
+fn get_pair_command() -> CreateApplicationCommand {
+    let mut pair = CreateApplicationCommand::default();
+    pair.name("pair")
+        .description("Chart a coin priced in another coin, eg. ETH/BTC")
+        .create_option(|option| {
+            option
+                .name("base")
+                .description("The coin being priced")
+                .kind(ApplicationCommandOptionType::String)
+                .required(true)
+                .set_autocomplete(true)
+        })
+        .create_option(|option| {
+            option
+                .name("quote")
+                .description("The coin to price it in")
+                .kind(ApplicationCommandOptionType::String)
+                .required(true)
+                .set_autocomplete(true)
+        })
+        .add_option(get_timeframe_option())
+        .add_option(get_graph_option())
+        .add_option(get_export_option(false));
+
+    pair
+}
+
+fn get_prices_command() -> CreateApplicationCommand {
+    let mut prices = CreateApplicationCommand::default();
+    prices
+        .name("prices")
+        .description("Quick quotes for several coins at once")
+        .create_option(|option| {
+            option
+                .name("coins")
+                .description("Coins separated by commas or spaces, eg. btc,eth,sol")
+                .kind(ApplicationCommandOptionType::String)
+                .required(true)
+        })
+        .add_option(get_currency_option());
+
+    prices
+}
+
+fn get_portfolio_command() -> CreateApplicationCommand {
+    let mut portfolio = CreateApplicationCommand::default();
+    portfolio
+        .name("portfolio")
+        .description("Track your holdings and how they're doing")
+        .create_option(|add| {
+            add.name("add")
+                .description("Add coins you bought just now")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .add_sub_option(get_portfolio_coin_option())
+                .add_sub_option(get_amount_option(true))
+                .add_sub_option(get_price_option("What you paid for each coin", true))
+                .add_sub_option(get_currency_option())
+        })
+        .create_option(|view| {
+            view.name("view")
+                .description("Show your portfolio's value and allocation")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .add_sub_option(get_timeframe_option())
+                .add_sub_option(get_export_option(false))
+        })
+        .create_option(|remove| {
+            remove
+                .name("remove")
+                .description("Stop tracking coins, moving them out at cost")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .add_sub_option(get_portfolio_coin_option())
+                .add_sub_option(get_amount_option(false))
+        })
+        .create_option(|tx| {
+            tx.name("tx")
+                .description("Record buys, sells and transfers")
+                .kind(ApplicationCommandOptionType::SubCommandGroup)
+                .create_sub_option(|buy| {
+                    buy.name("buy")
+                        .description("Record a purchase")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .add_sub_option(get_portfolio_coin_option())
+                        .add_sub_option(get_amount_option(true))
+                        .add_sub_option(get_price_option("What you paid for each coin", true))
+                        .add_sub_option(get_date_option())
+                        .add_sub_option(get_currency_option())
+                })
+                .create_sub_option(|sell| {
+                    sell.name("sell")
+                        .description("Record a sale")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .add_sub_option(get_portfolio_coin_option())
+                        .add_sub_option(get_amount_option(true))
+                        .add_sub_option(get_price_option("What you got for each coin", true))
+                        .add_sub_option(get_date_option())
+                        .add_sub_option(get_currency_option())
+                })
+                .create_sub_option(|transfer| {
+                    transfer
+                        .name("transfer")
+                        .description("Record coins moving in or out, without buying or selling")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .add_sub_option(get_portfolio_coin_option())
+                        .create_sub_option(|option| {
+                            option
+                                .name("direction")
+                                .description("Whether the coins came in or went out")
+                                .kind(ApplicationCommandOptionType::String)
+                                .required(true)
+                                .add_string_choice("In", "in")
+                                .add_string_choice("Out", "out")
+                        })
+                        .add_sub_option(get_amount_option(true))
+                        .add_sub_option(get_price_option(
+                            "Cost basis per coin for transfers in. Default is: the current price",
+                            false,
+                        ))
+                        .add_sub_option(get_date_option())
+                        .add_sub_option(get_currency_option())
+                })
+                .create_sub_option(|list| {
+                    list.name("list")
+                        .description("List your transactions")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                })
+                .create_sub_option(|delete| {
+                    delete
+                        .name("delete")
+                        .description("Delete a transaction")
+                        .kind(ApplicationCommandOptionType::SubCommand)
+                        .create_sub_option(|option| {
+                            option
+                                .name("id")
+                                .description("The transaction's number, see /portfolio tx list")
+                                .kind(ApplicationCommandOptionType::Integer)
+                                .required(true)
+                        })
+                })
+        })
+        .create_option(|report| {
+            report
+                .name("report")
+                .description("Realized and unrealized profit per coin")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("method")
+                        .description("How sales are matched to purchases. Default is: FIFO")
+                        .kind(ApplicationCommandOptionType::String);
+
+                    CostMethod::ALL.iter().for_each(|method| {
+                        option.add_string_choice(method.label(), method.name());
+                    });
+                    option
+                })
+        })
+        .create_option(|import| {
+            import
+                .name("import")
+                .description("Add transactions from a CSV file, eg. an exchange's export")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("file")
+                        .description("A CSV file with a header row")
+                        .kind(ApplicationCommandOptionType::Attachment)
+                        .required(true)
+                })
+                .add_sub_option(get_column_option("date"))
+                .add_sub_option(get_column_option("type"))
+                .add_sub_option(get_column_option("coin"))
+                .add_sub_option(get_column_option("amount"))
+                .add_sub_option(get_column_option("price"))
+                .add_sub_option(get_column_option("currency"))
+                .add_sub_option(get_currency_option())
+        })
+        .create_option(|export| {
+            export
+                .name("export")
+                .description("Download your transactions as a CSV file")
+                .kind(ApplicationCommandOptionType::SubCommand)
+        });
+
+    portfolio
+}
+
+fn get_portfolio_coin_option() -> CreateApplicationCommandOption {
+    let mut coin_option = CreateApplicationCommandOption::default();
+    coin_option
+        .name("coin")
+        .description("The coin's name, symbol or id")
+        .kind(ApplicationCommandOptionType::String)
+        .required(true)
+        .set_autocomplete(true);
+
+    coin_option
+}
+
+fn get_amount_option(required: bool) -> CreateApplicationCommandOption {
+    let description = match required {
+        true => "How many coins",
+        false => "How many coins. Default is: all of them",
+    };
+
+    let mut amount_option = CreateApplicationCommandOption::default();
+    amount_option
+        .name("amount")
+        .description(description)
+        .kind(ApplicationCommandOptionType::Number)
+        .required(required)
+        .min_number_value(0.0);
+
+    amount_option
+}
+
+fn get_price_option(description: &str, required: bool) -> CreateApplicationCommandOption {
+    let mut price_option = CreateApplicationCommandOption::default();
+    price_option
+        .name("price")
+        .description(description)
+        .kind(ApplicationCommandOptionType::Number)
+        .required(required)
+        .min_number_value(0.0);
+
+    price_option
+}
+
+fn get_date_option() -> CreateApplicationCommandOption {
+    let mut date_option = CreateApplicationCommandOption::default();
+    date_option
+        .name("date")
+        .description("When it happened, eg. 2022-06-01. Default is: now")
+        .kind(ApplicationCommandOptionType::String);
+
+    date_option
+}
+
+/// Which column of an imported file holds `field`. By default it's the column named after it.
+fn get_column_option(field: &str) -> CreateApplicationCommandOption {
+    let mut column_option = CreateApplicationCommandOption::default();
+    column_option.name(format!("{}_column", field));
+    column_option.description(format!(
+        "The header of the {} column. Default is: {}",
+        field, field
+    ));
+    column_option.kind(ApplicationCommandOptionType::String);
+
+    column_option
+}
+
+fn get_theme_command() -> CreateApplicationCommand {
+    let mut theme = CreateApplicationCommand::default();
+    theme
+        .name("theme")
+        .description("Choose how this server's charts look")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .dm_permission(false)
+        .create_option(|option| {
+            option
+                .name("palette")
+                .description("Chart colours. Default is: Dark")
+                .kind(ApplicationCommandOptionType::String);
+
+            Palette::ALL.iter().for_each(|palette| {
+                option.add_string_choice(palette.label(), palette.name());
+            });
+            option
+        })
+        .create_option(|option| {
+            option
+                .name("accent")
+                .description("Draw line charts in the coin's logo colour. Default is: off")
+                .kind(ApplicationCommandOptionType::Boolean)
+        });
+
+    theme
+}
+
+fn get_watchlist_command() -> CreateApplicationCommand {
+    let mut watchlist = CreateApplicationCommand::default();
+    watchlist
+        .name("watchlist")
+        .description("Keep an eye on several coins at once")
+        .create_option(|show| {
+            show.name("show")
+                .description("Show every coin on the watchlist in one table")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .add_sub_option(get_currency_option())
+                .add_sub_option(get_server_option())
+        })
+        .create_option(|add| {
+            add.name("add")
+                .description("Add coins to the watchlist")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .add_sub_option(get_watchlist_coins_option())
+                .add_sub_option(get_server_option())
+        })
+        .create_option(|remove| {
+            remove
+                .name("remove")
+                .description("Take coins off the watchlist")
+                .kind(ApplicationCommandOptionType::SubCommand)
+                .add_sub_option(get_watchlist_coins_option())
+                .add_sub_option(get_server_option())
+        });
+
+    watchlist
+}
+
+fn get_watchlist_coins_option() -> CreateApplicationCommandOption {
+    let mut coins_option = CreateApplicationCommandOption::default();
+    coins_option
+        .name("coins")
+        .description("Coins separated by commas or spaces, eg. bitcoin ethereum solana")
+        .kind(ApplicationCommandOptionType::String)
+        .required(true);
+
+    coins_option
+}
+
+fn get_server_option() -> CreateApplicationCommandOption {
+    let mut server_option = CreateApplicationCommandOption::default();
+    server_option
+        .name("server")
+        .description("Use the server's shared watchlist instead of yours. Default is: false")
+        .kind(ApplicationCommandOptionType::Boolean);
+
+    server_option
 }
 
 #[tokio::main]
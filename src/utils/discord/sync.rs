@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::request::RequestBuilder;
+use serenity::http::routing::RouteInfo;
+use serenity::http::Http;
+use serenity::json::{hashmap_to_json_map, json, Value};
+use serenity::model::id::GuildId;
+
+/// Where commands get registered.
+#[derive(Debug, Clone, Copy)]
+pub enum CommandScope {
+    Global,
+    Guild(GuildId),
+}
+
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub created: Vec<String>,
+    pub edited: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl SyncSummary {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.edited.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Brings the registered commands in line with `desired`, only touching the commands
+/// that were added, changed or removed.
+pub async fn sync_commands(
+    http: &Http,
+    application_id: u64,
+    scope: CommandScope,
+    desired: Vec<CreateApplicationCommand>,
+) -> Result<SyncSummary> {
+    let existing = get_registered_commands(http, application_id, scope).await?;
+    let mut existing_by_name: HashMap<String, (u64, Value)> = existing
+        .into_iter()
+        .filter_map(|command| {
+            let name = command["name"].as_str()?.to_string();
+            let id = command["id"].as_str()?.parse::<u64>().ok()?;
+            Some((name, (id, normalize_command(&command))))
+        })
+        .collect();
+
+    let desired: Vec<(String, Value)> = desired
+        .into_iter()
+        .map(|command| {
+            let command = Value::from(hashmap_to_json_map(command.0));
+            let name = command["name"].as_str().unwrap_or_default().to_string();
+            (name, command)
+        })
+        .collect();
+
+    let mut summary = SyncSummary::default();
+
+    // Stale commands go first, so they free up room under Discord's limit for the new ones,
+    // eg. when a coin dropped out of the top list and another took its place
+    let stale: Vec<String> = existing_by_name
+        .keys()
+        .filter(|name| !desired.iter().any(|(desired, _)| desired == *name))
+        .cloned()
+        .collect();
+    for name in stale {
+        if let Some((id, _)) = existing_by_name.remove(&name) {
+            match scope {
+                CommandScope::Global => http.delete_global_application_command(id).await?,
+                CommandScope::Guild(guild) => {
+                    http.delete_guild_application_command(guild.0, id).await?
+                }
+            };
+            summary.deleted.push(name);
+        }
+    }
+
+    for (name, command) in desired {
+        match existing_by_name.remove(&name) {
+            Some((_, current)) if current == normalize_command(&command) => {}
+            Some((id, _)) => {
+                match scope {
+                    CommandScope::Global => {
+                        http.edit_global_application_command(id, &command).await?
+                    }
+                    CommandScope::Guild(guild) => {
+                        http.edit_guild_application_command(guild.0, id, &command)
+                            .await?
+                    }
+                };
+                summary.edited.push(name);
+            }
+            None => {
+                match scope {
+                    CommandScope::Global => {
+                        http.create_global_application_command(&command).await?
+                    }
+                    CommandScope::Guild(guild) => {
+                        http.create_guild_application_command(guild.0, &command)
+                            .await?
+                    }
+                };
+                summary.created.push(name);
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Fetches the raw command JSON, since serenity's model drops fields such as `autocomplete`.
+async fn get_registered_commands(
+    http: &Http,
+    application_id: u64,
+    scope: CommandScope,
+) -> Result<Vec<Value>> {
+    let route = match scope {
+        CommandScope::Global => RouteInfo::GetGlobalApplicationCommands { application_id },
+        CommandScope::Guild(guild) => RouteInfo::GetGuildApplicationCommands {
+            application_id,
+            guild_id: guild.0,
+        },
+    };
+
+    Ok(http.fire(RequestBuilder::new(route).build()).await?)
+}
+
+/// Keeps only the fields we set, filling in Discord's defaults so both sides compare equal.
+fn normalize_command(command: &Value) -> Value {
+    json!({
+        "name": command["name"],
+        "description": command["description"],
+        // Permissions are a string on both sides, `null` when anyone may use the command
+        "default_member_permissions": command["default_member_permissions"],
+        "dm_permission": command["dm_permission"].as_bool().unwrap_or(true),
+        "options": normalize_options(&command["options"]),
+    })
+}
+
+fn normalize_options(options: &Value) -> Value {
+    let options = options.as_array().cloned().unwrap_or_default();
+
+    options
+        .iter()
+        .map(|option| {
+            let choices: Vec<Value> = option["choices"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+                .iter()
+                .map(|choice| json!({ "name": choice["name"], "value": choice["value"] }))
+                .collect();
+
+            json!({
+                "type": option["type"],
+                "name": option["name"],
+                "description": option["description"],
+                "required": option["required"].as_bool().unwrap_or(false),
+                "autocomplete": option["autocomplete"].as_bool().unwrap_or(false),
+                "choices": choices,
+                "min_value": normalize_number(&option["min_value"]),
+                "max_value": normalize_number(&option["max_value"]),
+                "options": normalize_options(&option["options"]),
+            })
+        })
+        .collect()
+}
+
+/// Bounds as floats, as Discord may send back `1.0` for a bound that was set as `1`.
+fn normalize_number(number: &Value) -> Value {
+    match number.as_f64() {
+        Some(number) => json!(number),
+        None => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::discord::{get_alert_command, get_theme_command};
+
+    fn to_value(command: CreateApplicationCommand) -> Value {
+        Value::from(hashmap_to_json_map(command.0))
+    }
+
+    /// Fills in an option the way Discord sends it back: explicit defaults, integer bounds
+    /// and localization fields on every choice.
+    fn as_returned_option(option: &Value) -> Value {
+        let mut option = option.clone();
+        let fields = option.as_object_mut().unwrap();
+        fields.entry("required").or_insert(json!(false));
+        fields.entry("autocomplete").or_insert(json!(false));
+        fields.insert("name_localizations".into(), Value::Null);
+        fields.insert("description_localizations".into(), Value::Null);
+        if let Some(min) = fields.get("min_value").and_then(Value::as_f64) {
+            fields.insert("min_value".into(), json!(min as i64));
+        }
+        if let Some(choices) = fields.get_mut("choices").and_then(Value::as_array_mut) {
+            for choice in choices {
+                choice["name_localizations"] = Value::Null;
+            }
+        }
+        if let Some(options) = fields.get_mut("options").and_then(Value::as_array_mut) {
+            for option in options {
+                *option = as_returned_option(option);
+            }
+        }
+
+        option
+    }
+
+    #[test]
+    fn registered_command_matches_its_builder() {
+        let registered = json!({
+            "id": "1022181515476770877",
+            "application_id": "817408196053696542",
+            "version": "1022181515476770878",
+            "type": 1,
+            "name": "theme",
+            "name_localizations": null,
+            "description": "Choose how this server's charts look",
+            "description_localizations": null,
+            "default_permission": true,
+            "default_member_permissions": "32",
+            "dm_permission": false,
+            "nsfw": false,
+            "options": [
+                {
+                    "type": 3,
+                    "name": "palette",
+                    "description": "Chart colours. Default is: Dark",
+                    "required": false,
+                    "choices": [
+                        { "name": "Dark", "name_localizations": null, "value": "dark" },
+                        { "name": "Light", "name_localizations": null, "value": "light" },
+                        {
+                            "name": "High contrast",
+                            "name_localizations": null,
+                            "value": "high-contrast"
+                        }
+                    ]
+                },
+                {
+                    "type": 5,
+                    "name": "accent",
+                    "description": "Draw line charts in the coin's logo colour. Default is: off"
+                }
+            ]
+        });
+
+        assert_eq!(
+            normalize_command(&registered),
+            normalize_command(&to_value(get_theme_command()))
+        );
+    }
+
+    #[test]
+    fn registered_subcommands_match_their_builder() {
+        let local = to_value(get_alert_command());
+        let mut registered = as_returned_option(&local);
+        registered["id"] = json!("1022181515476770879");
+        registered["version"] = json!("1022181515476770880");
+        registered["type"] = json!(1);
+        registered["dm_permission"] = json!(true);
+        registered["default_member_permissions"] = Value::Null;
+        registered.as_object_mut().unwrap().remove("required");
+        registered.as_object_mut().unwrap().remove("autocomplete");
+
+        assert_eq!(
+            registered["options"][0]["options"][1]["min_value"],
+            json!(0)
+        );
+        assert_eq!(normalize_command(&registered), normalize_command(&local));
+    }
+
+    #[test]
+    fn changed_command_does_not_match() {
+        let local = to_value(get_alert_command());
+        let mut registered = local.clone();
+        registered["options"][0]["options"][1]["description"] = json!("Rises above");
+        assert_ne!(normalize_command(&registered), normalize_command(&local));
+
+        let mut registered = local.clone();
+        registered["options"][0]["options"][1]["min_value"] = json!(1);
+        assert_ne!(normalize_command(&registered), normalize_command(&local));
+    }
+}
@@ -1,11 +1,32 @@
 use std::env;
 
-use super::lib::{Arg, CommandInfo};
+use super::lib::{Arg, Attachment, CommandInfo};
+use crate::utils::export::Export;
+use crate::utils::gecko::get_provider;
+use crate::utils::gecko::lib::{GeckoError, GeckoErrorKind, Timeframe};
+use crate::utils::gecko::resolver::{resolve_coin, Resolution};
+use crate::utils::plotter::indicators::Indicator;
 use anyhow::Result;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
 use rusty_money::iso::{self, Currency};
+use serenity::http::multipart::Multipart;
+use serenity::http::request::RequestBuilder;
+use serenity::http::routing::RouteInfo;
+use serenity::http::Http;
+use serenity::json::{hashmap_to_json_map, json, Value};
+use serenity::utils::Colour;
 use serenity::{
+    builder::{CreateComponents, CreateEmbed, EditInteractionResponse},
     client::Context,
-    model::{id::UserId, interactions::application_command::ApplicationCommandInteraction},
+    model::{
+        channel::{AttachmentType, Message},
+        id::UserId,
+        interactions::{
+            application_command::{ApplicationCommandInteraction, ApplicationCommandOptionType},
+            message_component::MessageComponentInteraction,
+        },
+    },
 };
 
 lazy_static! {
@@ -22,22 +43,36 @@ fn get_owner_id() -> u64 {
 }
 
 pub fn get_command_info(command: &ApplicationCommandInteraction) -> Result<CommandInfo> {
-    let args = command
-        .data
-        .options
+    let mut subcommands = vec![];
+    let mut options = &command.data.options;
+
+    // Subcommand arguments are nested inside their (group and) subcommand
+    while let Some(option) = options.first() {
+        match option.kind {
+            ApplicationCommandOptionType::SubCommand
+            | ApplicationCommandOptionType::SubCommandGroup => {
+                subcommands.push(option.name.as_str());
+                options = &option.options;
+            }
+            _ => break,
+        }
+    }
+
+    let args = options
         .iter()
-        .map(|option| {
-            let value = option.value.as_ref().unwrap();
+        .filter_map(|option| {
+            let value = option.value.as_ref()?;
 
-            Arg {
+            Some(Arg {
                 name: &option.name,
                 value: value.clone(),
-            }
+            })
         })
         .collect();
 
     Ok(CommandInfo {
         name: &command.data.name,
+        subcommands,
         args: args,
     })
 }
@@ -75,15 +110,257 @@ pub fn get_graph_option(command: &ApplicationCommandInteraction) -> Result<Strin
     }
 }
 
+pub fn get_timeframe_option(command: &ApplicationCommandInteraction) -> Result<Timeframe> {
+    let command_info = get_command_info(command)?;
+    let timeframe_arg = command_info.get_arg("timeframe");
+
+    let default = Ok(Timeframe::Week);
+
+    match timeframe_arg {
+        Some(timeframe) => match timeframe.as_str().and_then(Timeframe::from_name) {
+            Some(timeframe) => Ok(timeframe),
+            None => default,
+        },
+        None => default,
+    }
+}
+
+pub fn get_indicators_option(command: &ApplicationCommandInteraction) -> Result<Vec<Indicator>> {
+    let command_info = get_command_info(command)?;
+    let indicators_arg = command_info.get_arg("indicators");
+
+    match indicators_arg {
+        Some(indicators) => Indicator::parse_list(indicators.as_str().unwrap_or_default()),
+        None => Ok(vec![]),
+    }
+}
+
+pub fn get_export_option(command: &ApplicationCommandInteraction) -> Result<Option<Export>> {
+    let command_info = get_command_info(command)?;
+    let export_arg = command_info.get_arg("export");
+
+    Ok(export_arg.and_then(|export| export.as_str().and_then(Export::from_name)))
+}
+
+/// A coin as the user typed it, to be resolved with [`resolve_coin_arg`].
+pub fn get_coin_arg(command_info: &CommandInfo, name: &str) -> String {
+    command_info
+        .get_arg(name)
+        .unwrap_or(Value::String("".into()))
+        .as_str()
+        .unwrap_or_default()
+        .trim()
+        .into()
+}
+
+pub fn get_decimal_arg(command_info: &CommandInfo, name: &str) -> Option<Decimal> {
+    command_info
+        .get_arg(name)
+        .and_then(|value| value.as_f64())
+        .and_then(Decimal::from_f64)
+}
+
+/// The `coins` argument, separated by commas, spaces or both, eg. `btc, eth sol`.
+pub fn get_coins_arg(command_info: &CommandInfo) -> Vec<String> {
+    command_info
+        .get_arg("coins")
+        .unwrap_or(Value::String("".into()))
+        .as_str()
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|coin| !coin.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// A coin's brand colour for its embed, or the usual gold when there isn't one.
+pub fn get_embed_colour(color: Option<(u8, u8, u8)>) -> Colour {
+    match color {
+        Some((r, g, b)) => Colour::from_rgb(r, g, b),
+        None => Colour::DARK_GOLD,
+    }
+}
+
+/// Fills the deferred reply with an embed, its components and attached files, such as the
+/// chart the embed shows.
+pub async fn send_embed(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    embed: CreateEmbed,
+    components: CreateComponents,
+    attachments: Vec<Attachment>,
+) -> Result<()> {
+    let mut response = EditInteractionResponse::default();
+    // Clear any placeholder text, such as the busy notice
+    response.content("").set_embed(embed).components(|c| {
+        *c = components;
+        c
+    });
+
+    edit_original_with_file(
+        &ctx.http,
+        command.application_id.0,
+        &command.token,
+        response,
+        attachments,
+    )
+    .await
+}
+
+/// Replaces the embed, components and attached files of the message a component belongs to.
+pub async fn update_embed(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+    embed: CreateEmbed,
+    components: CreateComponents,
+    attachments: Vec<Attachment>,
+) -> Result<()> {
+    let mut response = EditInteractionResponse::default();
+    response.set_embed(embed).components(|c| {
+        *c = components;
+        c
+    });
+
+    edit_original_with_file(
+        &ctx.http,
+        component.application_id.0,
+        &component.token,
+        response,
+        attachments,
+    )
+    .await
+}
+
+/// Fills the deferred reply with text and a file, such as an export.
+pub async fn send_file(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: String,
+    attachment: Attachment,
+) -> Result<()> {
+    let mut response = EditInteractionResponse::default();
+    response.content(content);
+
+    edit_original_with_file(
+        &ctx.http,
+        command.application_id.0,
+        &command.token,
+        response,
+        vec![attachment],
+    )
+    .await
+}
+
+// Serenity can't attach files when editing an original response, so the request is built here
+async fn edit_original_with_file(
+    http: &Http,
+    application_id: u64,
+    token: &str,
+    response: EditInteractionResponse,
+    attachments: Vec<Attachment>,
+) -> Result<()> {
+    let mut map = hashmap_to_json_map(response.0);
+    // Listing only the new uploads drops the previously attached files
+    let ids: Vec<Value> = (0..attachments.len())
+        .map(|id| json!({ "id": id }))
+        .collect();
+    map.insert("attachments".into(), Value::from(ids));
+
+    let mut request = RequestBuilder::new(RouteInfo::EditOriginalInteractionResponse {
+        application_id,
+        interaction_token: token,
+    });
+    request.multipart(Some(Multipart {
+        files: attachments
+            .into_iter()
+            .map(|attachment| AttachmentType::Bytes {
+                data: attachment.data.into(),
+                filename: attachment.filename,
+            })
+            .collect(),
+        fields: vec![],
+        payload_json: Some(Value::from(map)),
+    }));
+
+    http.fire::<Message>(request.build()).await?;
+
+    Ok(())
+}
+
+/// Fills the deferred reply with plain text.
+pub async fn send_content(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: String,
+) -> Result<()> {
+    command
+        .edit_original_interaction_response(&ctx.http, |r| r.content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// Fills the deferred reply with an embed that has no chart.
+pub async fn send_text_embed(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    embed: CreateEmbed,
+) -> Result<()> {
+    command
+        .edit_original_interaction_response(&ctx.http, |r| r.content("").set_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Resolves a user-supplied coin to its id.
+///
+/// When the input is ambiguous the user is sent a list of candidates instead and
+/// `None` is returned.
+pub async fn resolve_coin_arg(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    query: &str,
+) -> Result<Option<String>> {
+    match resolve_coin(get_provider(), query).await? {
+        Resolution::Found(info) => Ok(Some(info.id)),
+        Resolution::Ambiguous(candidates) => {
+            let suggestions = candidates
+                .iter()
+                .map(|coin| {
+                    format!(
+                        "• {} ({}) → `{}`",
+                        coin.name,
+                        coin.symbol.to_uppercase(),
+                        coin.id
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            let content = format!("Did you mean one of these?\n{}", suggestions);
+            send_content(ctx, command, content).await?;
+            Ok(None)
+        }
+        Resolution::NotFound => {
+            let err =
+                GeckoError::new(GeckoErrorKind::CoinNotFound, "/coins/list").with_coin(Some(query));
+            Err(err.into())
+        }
+    }
+}
+
+/// Sends the owner a DM. Background tasks report through this too, so a failed DM is only
+/// printed rather than taking the task down with it.
 pub async fn message_owner(ctx: &Context, message: String) {
-    let vvv = UserId(*OWNER_ID)
-        .to_user(ctx.clone())
-        .await
-        .expect("Failed to retrieve owner");
+    let sent = match UserId(*OWNER_ID).to_user(ctx.clone()).await {
+        Ok(owner) => owner.direct_message(&ctx, |m| m.content(&message)).await,
+        Err(err) => Err(err),
+    };
 
-    vvv.direct_message(&ctx, |m| m.content(message))
-        .await
-        .expect("Error sending dm to owner");
+    if let Err(err) = sent {
+        println!("Error sending dm to owner: {:?}\n{}", err, message);
+    }
 }
 
 pub fn get_invoking_user(command: &ApplicationCommandInteraction) -> String {
@@ -98,3 +375,30 @@ pub fn get_invoking_user(command: &ApplicationCommandInteraction) -> String {
         _ => "Anonymous".into(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_coins(coins: &str) -> Vec<String> {
+        let command_info = CommandInfo {
+            name: "prices",
+            subcommands: vec![],
+            args: vec![Arg {
+                name: "coins",
+                value: Value::String(coins.into()),
+            }],
+        };
+        get_coins_arg(&command_info)
+    }
+
+    #[test]
+    fn coins_split_on_commas_and_whitespace() {
+        let expected = vec!["btc", "eth", "sol"];
+        assert_eq!(get_coins("btc,eth,sol"), expected);
+        assert_eq!(get_coins("btc eth sol"), expected);
+        assert_eq!(get_coins(" btc, eth  sol,,"), expected);
+        assert_eq!(get_coins("btc,\teth\nsol"), expected);
+        assert!(get_coins(" , ").is_empty());
+    }
+}
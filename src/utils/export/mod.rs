@@ -0,0 +1,142 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+/// Which data to attach as CSV next to a chart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Export {
+    /// The series the chart was drawn from
+    Chart,
+    /// The coin's hourly prices over the last 7 days
+    Sparkline,
+}
+
+impl Export {
+    pub const ALL: [Export; 2] = [Export::Chart, Export::Sparkline];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Export::Chart => "chart",
+            Export::Sparkline => "sparkline",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Export::Chart => "Chart data",
+            Export::Sparkline => "7d sparkline",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Export::ALL
+            .iter()
+            .find(|export| export.name() == name)
+            .copied()
+    }
+}
+
+/// A CSV file, ready to be attached.
+pub struct Sheet {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+impl Sheet {
+    /// Writes `rows` under `headers`, naming the file after `name` like a chart.
+    pub fn from_rows<R: Serialize>(name: &str, headers: &[&str], rows: &[R]) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record(headers)?;
+        for row in rows {
+            writer.serialize(row)?;
+        }
+
+        Ok(Sheet {
+            filename: format!("{}_{}.csv", name, Local::now().format("%Y%m%d%H%M%S%f")),
+            data: writer.into_inner()?,
+        })
+    }
+}
+
+/// Prices, market caps and volumes of a market chart. They're sampled together, so rows are
+/// matched up by position.
+pub fn get_market_chart_sheet(
+    name: &str,
+    prices: &[(f64, f64)],
+    market_caps: &[(f64, f64)],
+    volumes: &[(f64, f64)],
+) -> Result<Sheet> {
+    let rows: Vec<(String, f64, Option<f64>, Option<f64>)> = prices
+        .iter()
+        .enumerate()
+        .map(|(i, (time, price))| {
+            (
+                format_time(*time),
+                *price,
+                market_caps.get(i).map(|(_, cap)| *cap),
+                volumes.get(i).map(|(_, volume)| *volume),
+            )
+        })
+        .collect();
+
+    Sheet::from_rows(name, &["time", "price", "market_cap", "volume"], &rows)
+}
+
+/// Candles in the `[timestamp, open, high, low, close]` form CoinGecko returns them in.
+pub fn get_ohlc_sheet(name: &str, candles: &[Vec<f64>]) -> Result<Sheet> {
+    let rows: Vec<(String, f64, f64, f64, f64)> = candles
+        .iter()
+        .filter(|candle| candle.len() >= 5)
+        .map(|candle| {
+            (
+                format_time(candle[0]),
+                candle[1],
+                candle[2],
+                candle[3],
+                candle[4],
+            )
+        })
+        .collect();
+
+    Sheet::from_rows(name, &["time", "open", "high", "low", "close"], &rows)
+}
+
+/// Several named series in one sheet, a row per point. Series don't share their timestamps,
+/// so they're listed one after another rather than side by side.
+pub fn get_series_sheet(name: &str, series: &[(String, Vec<(f64, f64)>)]) -> Result<Sheet> {
+    let rows: Vec<(&str, String, f64)> = series
+        .iter()
+        .flat_map(|(label, points)| {
+            points
+                .iter()
+                .map(move |(time, value)| (label.as_str(), format_time(*time), *value))
+        })
+        .collect();
+
+    Sheet::from_rows(name, &["series", "time", "value"], &rows)
+}
+
+/// Sparklines of several coins. CoinGecko leaves out their times, the points are hourly
+/// and end now, so each gets its offset in hours instead.
+pub fn get_sparkline_sheet(name: &str, sparklines: &[(String, Vec<f64>)]) -> Result<Sheet> {
+    let rows: Vec<(&str, i64, f64)> = sparklines
+        .iter()
+        .flat_map(|(label, prices)| {
+            let last = prices.len() as i64 - 1;
+            prices
+                .iter()
+                .enumerate()
+                .map(move |(i, price)| (label.as_str(), last - i as i64, *price))
+        })
+        .collect();
+
+    Sheet::from_rows(name, &["coin", "hours_ago", "price"], &rows)
+}
+
+/// A millisecond timestamp as an RFC 3339 date, which spreadsheets read as a date.
+fn format_time(time: f64) -> String {
+    let time = time as i64;
+    let date = NaiveDateTime::from_timestamp(time / 1000, (time % 1000 * 1_000_000) as u32);
+
+    DateTime::<Utc>::from_utc(date, Utc).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use rusty_money::iso::Currency;
+use tokio::sync::OnceCell;
+
+use super::lib::{Coin, CoinInfo, CoinMarket, GeckoError, MarketChart, SimplePrices, Timeframe};
+use super::provider::MarketDataProvider;
+
+const COIN_TTL: Duration = Duration::from_secs(60);
+const OHLC_TTL: Duration = Duration::from_secs(5 * 60);
+const CHART_TTL: Duration = Duration::from_secs(5 * 60);
+const LIST_TTL: Duration = Duration::from_secs(60 * 60);
+const MARKETS_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+type Slot<V> = Arc<OnceCell<(V, Instant)>>;
+
+struct Entries<V> {
+    slots: HashMap<String, Slot<V>>,
+    swept_at: Instant,
+}
+
+/// An async cache whose entries expire `ttl` after being fetched.
+///
+/// Callers asking for a key that is still being fetched wait on that same fetch
+/// instead of starting their own.
+pub struct TtlCache<V> {
+    ttl: Duration,
+    entries: Mutex<Entries<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration) -> Self {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(Entries {
+                slots: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn get_or_fetch<F, Fut, E>(&self, key: String, fetch: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let slot = self.get_slot(key);
+
+        let mut fetched = false;
+        let result = slot
+            .get_or_try_init(|| async {
+                fetched = true;
+                fetch().await.map(|value| (value, Instant::now()))
+            })
+            .await;
+
+        // A failed fetch is still a trip to the API
+        let counter = if fetched { &self.misses } else { &self.hits };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        result.map(|(value, _)| value.clone())
+    }
+
+    /// The key's slot, replacing it when its value has gone stale. Other stale entries are
+    /// only swept when a slot gets added, and at most once per `ttl`.
+    fn get_slot(&self, key: String) -> Slot<V> {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        let is_stale = |slot: &Slot<V>| match slot.get() {
+            Some((_, fetched_at)) => fetched_at.elapsed() >= ttl,
+            None => false,
+        };
+
+        if let Some(slot) = entries.slots.get(&key) {
+            if !is_stale(slot) {
+                return slot.clone();
+            }
+        }
+
+        if entries.swept_at.elapsed() >= ttl {
+            entries.slots.retain(|_, slot| !is_stale(slot));
+            entries.swept_at = Instant::now();
+        }
+
+        let slot = Slot::default();
+        entries.slots.insert(key, slot.clone());
+
+        slot
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Wraps a provider so repeated coin, chart and list lookups are served from memory.
+pub struct CachedProvider {
+    inner: Box<dyn MarketDataProvider>,
+    coins: TtlCache<Coin>,
+    ohlc: TtlCache<Vec<Vec<f64>>>,
+    charts: TtlCache<MarketChart>,
+    list: TtlCache<Vec<CoinInfo>>,
+    markets: TtlCache<Vec<CoinMarket>>,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Box<dyn MarketDataProvider>) -> Self {
+        CachedProvider {
+            inner,
+            coins: TtlCache::new(COIN_TTL),
+            ohlc: TtlCache::new(OHLC_TTL),
+            charts: TtlCache::new(CHART_TTL),
+            list: TtlCache::new(LIST_TTL),
+            markets: TtlCache::new(MARKETS_TTL),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        [
+            self.coins.stats(),
+            self.ohlc.stats(),
+            self.charts.stats(),
+            self.list.stats(),
+            self.markets.stats(),
+        ]
+        .iter()
+        .fold(CacheStats::default(), |total, stats| CacheStats {
+            hits: total.hits + stats.hits,
+            misses: total.misses + stats.misses,
+        })
+    }
+}
+
+#[async_trait]
+impl MarketDataProvider for CachedProvider {
+    async fn get_coin(&self, coin: &str) -> Result<Coin, GeckoError> {
+        let key = format!("coin:{}", coin.to_lowercase());
+
+        self.coins
+            .get_or_fetch(key, || self.inner.get_coin(coin))
+            .await
+    }
+
+    async fn get_ohlc(
+        &self,
+        coin: &str,
+        timeframe: Timeframe,
+        currency: Currency,
+    ) -> Result<Vec<Vec<f64>>, GeckoError> {
+        let key = format!(
+            "ohlc:{}:{}:{}",
+            coin.to_lowercase(),
+            currency.iso_alpha_code.to_lowercase(),
+            timeframe.days()
+        );
+
+        self.ohlc
+            .get_or_fetch(key, || self.inner.get_ohlc(coin, timeframe, currency))
+            .await
+    }
+
+    async fn get_market_chart(
+        &self,
+        coin: &str,
+        timeframe: Timeframe,
+        currency: Currency,
+    ) -> Result<MarketChart, GeckoError> {
+        let key = format!(
+            "chart:{}:{}:{}",
+            coin.to_lowercase(),
+            currency.iso_alpha_code.to_lowercase(),
+            timeframe.days()
+        );
+
+        self.charts
+            .get_or_fetch(key, || {
+                self.inner.get_market_chart(coin, timeframe, currency)
+            })
+            .await
+    }
+
+    async fn get_list(&self) -> Result<Vec<CoinInfo>, GeckoError> {
+        self.list
+            .get_or_fetch("list".into(), || self.inner.get_list())
+            .await
+    }
+
+    async fn get_top_coins(&self, count: u8) -> Result<Vec<CoinInfo>, GeckoError> {
+        self.inner.get_top_coins(count).await
+    }
+
+    async fn get_simple_prices(
+        &self,
+        ids: &[String],
+        currencies: &[String],
+    ) -> Result<SimplePrices, GeckoError> {
+        self.inner.get_simple_prices(ids, currencies).await
+    }
+
+    async fn get_markets(
+        &self,
+        ids: &[String],
+        currency: Currency,
+    ) -> Result<Vec<CoinMarket>, GeckoError> {
+        // The same coins in any order make the same request
+        let mut sorted = ids.to_vec();
+        sorted.sort();
+        let key = format!(
+            "markets:{}:{}",
+            currency.iso_alpha_code.to_lowercase(),
+            sorted.join(",")
+        );
+
+        self.markets
+            .get_or_fetch(key, || self.inner.get_markets(ids, currency))
+            .await
+    }
+
+    fn is_busy(&self) -> bool {
+        self.inner.is_busy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    async fn fetch(cache: &TtlCache<u32>, key: &str, calls: &AtomicUsize) -> Result<u32, ()> {
+        cache
+            .get_or_fetch(key.into(), || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(7)
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_ttl() {
+        let cache = TtlCache::new(Duration::from_millis(50));
+        let calls = AtomicUsize::new(0);
+
+        assert_eq!(fetch(&cache, "btc", &calls).await, Ok(7));
+        assert_eq!(fetch(&cache, "btc", &calls).await, Ok(7));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(fetch(&cache, "btc", &calls).await, Ok(7));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+    }
+
+    #[tokio::test]
+    async fn stale_entries_are_swept_on_insert() {
+        let cache = TtlCache::new(Duration::from_millis(50));
+        let calls = AtomicUsize::new(0);
+
+        fetch(&cache, "btc", &calls).await.unwrap();
+        fetch(&cache, "eth", &calls).await.unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        fetch(&cache, "sol", &calls).await.unwrap();
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.slots.keys().collect::<Vec<_>>(), vec!["sol"]);
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_a_fetch() {
+        let cache = TtlCache::new(Duration::from_secs(60));
+        let calls = AtomicUsize::new(0);
+
+        let (first, second) =
+            tokio::join!(fetch(&cache, "btc", &calls), fetch(&cache, "btc", &calls));
+
+        assert_eq!((first, second), (Ok(7), Ok(7)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn failed_fetches_are_misses_and_not_cached() {
+        let cache: TtlCache<u32> = TtlCache::new(Duration::from_secs(60));
+
+        let failed = cache.get_or_fetch("btc".into(), || async { Err("down") });
+        assert_eq!(failed.await, Err("down"));
+        let retried = cache.get_or_fetch("btc".into(), || async { Ok::<_, &str>(7) });
+        assert_eq!(retried.await, Ok(7));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 2));
+    }
+}
@@ -0,0 +1,92 @@
+use std::env;
+
+const PUBLIC_URL: &str = "https://api.coingecko.com/api/v3";
+const PRO_URL: &str = "https://pro-api.coingecko.com/api/v3";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiTier {
+    Public,
+    Demo,
+    Pro,
+}
+
+impl ApiTier {
+    fn base_url(&self) -> &'static str {
+        match self {
+            ApiTier::Public | ApiTier::Demo => PUBLIC_URL,
+            ApiTier::Pro => PRO_URL,
+        }
+    }
+
+    fn calls_per_minute(&self) -> u32 {
+        match self {
+            ApiTier::Public => 10,
+            ApiTier::Demo => 30,
+            ApiTier::Pro => 500,
+        }
+    }
+
+    pub fn key_header(&self) -> Option<&'static str> {
+        match self {
+            ApiTier::Public => None,
+            ApiTier::Demo => Some("x-cg-demo-api-key"),
+            ApiTier::Pro => Some("x-cg-pro-api-key"),
+        }
+    }
+}
+
+/// Connection settings shared by every CoinGecko request.
+#[derive(Debug, Clone)]
+pub struct GeckoConfig {
+    pub tier: ApiTier,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub calls_per_minute: u32,
+}
+
+impl GeckoConfig {
+    pub fn new(tier: ApiTier, api_key: Option<String>) -> Self {
+        GeckoConfig {
+            base_url: tier.base_url().into(),
+            calls_per_minute: tier.calls_per_minute(),
+            tier,
+            api_key,
+        }
+    }
+
+    /// Overrides the tier's default base url, eg. to point at a local mock server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').into();
+        self
+    }
+
+    /// Reads `GECKO_API_TIER` (public, demo or pro), `GECKO_API_KEY`, `GECKO_BASE_URL`
+    /// and `GECKO_CALLS_PER_MINUTE`.
+    pub fn from_env() -> Self {
+        let api_key = env::var("GECKO_API_KEY").ok().filter(|key| !key.is_empty());
+        let tier = match env::var("GECKO_API_TIER") {
+            Ok(tier) => match tier.to_lowercase().as_str() {
+                "public" => ApiTier::Public,
+                "demo" => ApiTier::Demo,
+                "pro" => ApiTier::Pro,
+                other => panic!("Unknown CoinGecko API tier: {}", other),
+            },
+            // A key without an explicit tier is most likely a free demo key
+            Err(_) if api_key.is_some() => ApiTier::Demo,
+            Err(_) => ApiTier::Public,
+        };
+
+        let mut config = GeckoConfig::new(tier, api_key);
+
+        if let Ok(calls) = env::var("GECKO_CALLS_PER_MINUTE") {
+            config.calls_per_minute = calls
+                .parse()
+                .expect("GECKO_CALLS_PER_MINUTE must be a number");
+        }
+
+        match env::var("GECKO_BASE_URL") {
+            Ok(base_url) if !base_url.is_empty() => config.with_base_url(&base_url),
+            _ => config,
+        }
+    }
+}
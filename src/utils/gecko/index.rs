@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serenity::client::Context;
+
+use super::lib::{CoinInfo, GeckoError};
+use super::provider::MarketDataProvider;
+use crate::utils::discord::message_owner;
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+const RANKED_COINS: u8 = 250;
+
+lazy_static! {
+    static ref INDEX: RwLock<Arc<Vec<CoinInfo>>> = RwLock::new(Arc::new(Vec::new()));
+    static ref REFRESHING: AtomicBool = AtomicBool::new(false);
+}
+
+/// The latest indexed coin list, ordered by market cap rank. Empty until the first refresh.
+pub fn get_snapshot() -> Arc<Vec<CoinInfo>> {
+    INDEX.read().unwrap().clone()
+}
+
+/// Rebuilds the index from the full coin list, ranked using the top coins by market cap.
+pub async fn refresh(provider: &dyn MarketDataProvider) -> Result<usize, GeckoError> {
+    let list = provider.get_list().await?;
+    let ranks: HashMap<String, u32> = provider
+        .get_top_coins(RANKED_COINS)
+        .await?
+        .into_iter()
+        .filter_map(|coin| coin.market_cap_rank.map(|rank| (coin.id, rank)))
+        .collect();
+
+    let mut coins: Vec<CoinInfo> = list
+        .into_iter()
+        .map(|coin| CoinInfo {
+            market_cap_rank: ranks.get(&coin.id).copied(),
+            ..coin
+        })
+        .collect();
+    coins.sort_by_key(|coin| coin.market_cap_rank.unwrap_or(u32::MAX));
+
+    let count = coins.len();
+    *INDEX.write().unwrap() = Arc::new(coins);
+
+    Ok(count)
+}
+
+/// Keeps the index fresh in the background. Only the first call starts the task.
+///
+/// Failures are reported to the owner once when they start rather than on every retry.
+pub fn spawn_refresh(provider: &'static dyn MarketDataProvider, ctx: Context) {
+    if REFRESHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut failing = false;
+
+        loop {
+            let delay = match refresh(provider).await {
+                Ok(_) => {
+                    failing = false;
+                    REFRESH_INTERVAL
+                }
+                Err(err) => {
+                    if !failing {
+                        let msg = format!("Coin index refresh failed: {}", err.details());
+                        message_owner(&ctx, msg).await;
+                    }
+                    failing = true;
+                    RETRY_INTERVAL
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
+/// Up to `limit` coins matching `query`; exact matches first, then prefixes, then
+/// substrings, each ordered by market cap.
+pub fn search(query: &str, limit: usize) -> Vec<CoinInfo> {
+    let snapshot = get_snapshot();
+    let query = query.trim().to_lowercase();
+
+    if query.is_empty() {
+        return snapshot.iter().take(limit).cloned().collect();
+    }
+
+    let mut matches: Vec<(u8, &CoinInfo)> = snapshot
+        .iter()
+        .filter_map(|coin| match_quality(&query, coin).map(|quality| (quality, coin)))
+        .collect();
+
+    // The snapshot is already ordered by rank, and the sort is stable
+    matches.sort_by_key(|(quality, _)| *quality);
+
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|(_, coin)| coin.clone())
+        .collect()
+}
+
+fn match_quality(query: &str, coin: &CoinInfo) -> Option<u8> {
+    let symbol = coin.symbol.to_lowercase();
+    let name = coin.name.to_lowercase();
+
+    if coin.id == query || symbol == query || name == query {
+        Some(0)
+    } else if symbol.starts_with(query) || name.starts_with(query) || coin.id.starts_with(query) {
+        Some(1)
+    } else if name.contains(query) || coin.id.contains(query) {
+        Some(2)
+    } else {
+        None
+    }
+}
@@ -1,39 +1,156 @@
 use regex::{Captures, Regex};
+use reqwest::StatusCode;
 use rust_decimal::Decimal;
 use rusty_money::{iso::Currency, Money};
 use serde::{Deserialize, Deserializer, Serialize};
-use std::{fmt, ops::Index};
+use std::{collections::HashMap, fmt, ops::Index};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeckoErrorKind {
+    /// The API key was rejected or doesn't cover the endpoint
+    Auth,
+    CoinNotFound,
+    Limit,
+    Parse,
+    Request,
+    Unknown,
+}
+
+#[derive(Debug)]
+pub enum GeckoErrorSource {
+    Request(reqwest::Error),
+    Parse(serde_path_to_error::Error<serde_json::Error>),
+}
+
+/// A failed market data request, along with where and why it failed.
 #[derive(Debug)]
-pub enum GeckoError {
-    CoinNotFoundError,
-    LimitError,
-    ParseError,
-    UnknownError,
+pub struct GeckoError {
+    pub kind: GeckoErrorKind,
+    pub endpoint: String,
+    pub coin: Option<String>,
+    pub status: Option<StatusCode>,
+    pub source: Option<GeckoErrorSource>,
 }
-impl std::error::Error for GeckoError {}
+
+impl GeckoError {
+    pub fn new(kind: GeckoErrorKind, endpoint: &str) -> Self {
+        GeckoError {
+            kind,
+            endpoint: endpoint.into(),
+            coin: None,
+            status: None,
+            source: None,
+        }
+    }
+
+    pub fn with_coin(mut self, coin: Option<&str>) -> Self {
+        self.coin = coin.map(|coin| coin.into());
+        self
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_source(mut self, source: GeckoErrorSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Whether the same request could succeed if it was simply sent again later.
+    pub fn is_retryable(&self) -> bool {
+        match self.kind {
+            GeckoErrorKind::Limit => true,
+            GeckoErrorKind::Request => match &self.source {
+                Some(GeckoErrorSource::Request(err)) => err.is_timeout() || err.is_connect(),
+                _ => false,
+            },
+            GeckoErrorKind::Unknown => {
+                matches!(self.status, Some(status) if status.is_server_error())
+            }
+            GeckoErrorKind::Auth | GeckoErrorKind::CoinNotFound | GeckoErrorKind::Parse => false,
+        }
+    }
+
+    /// The JSON path that failed to deserialize, for parse errors.
+    pub fn parse_path(&self) -> Option<String> {
+        match &self.source {
+            Some(GeckoErrorSource::Parse(err)) => Some(err.path().to_string()),
+            _ => None,
+        }
+    }
+
+    /// A full description of the failure, meant for the bot owner rather than users.
+    pub fn details(&self) -> String {
+        let mut details = format!("{:?} error on {}", self.kind, self.endpoint);
+
+        if let Some(coin) = &self.coin {
+            details = format!("{} for coin `{}`", details, coin);
+        }
+        if let Some(status) = self.status {
+            details = format!("{} (status {})", details, status);
+        }
+        if let Some(path) = self.parse_path() {
+            details = format!("{} at `{}`", details, path);
+        }
+        match &self.source {
+            Some(GeckoErrorSource::Request(err)) => details = format!("{}: {}", details, err),
+            Some(GeckoErrorSource::Parse(err)) => details = format!("{}: {}", details, err.inner()),
+            None => {}
+        }
+        if self.kind == GeckoErrorKind::Auth {
+            details = format!(
+                "{}. Check that GECKO_API_KEY is set and valid for GECKO_API_TIER",
+                details
+            );
+        }
+
+        format!("{} [retryable: {}]", details, self.is_retryable())
+    }
+}
+
+impl std::error::Error for GeckoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.source {
+            Some(GeckoErrorSource::Request(err)) => Some(err),
+            Some(GeckoErrorSource::Parse(err)) => Some(err),
+            None => None,
+        }
+    }
+}
+
 impl fmt::Display for GeckoError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            GeckoError::CoinNotFoundError => {
+        match self.kind {
+            GeckoErrorKind::Auth => {
+                write!(
+                    f,
+                    "The market data API turned me away, my owner has been told"
+                )
+            }
+            GeckoErrorKind::CoinNotFound => {
                 write!(f, "Coin not found! Try its full name, eg. bitcoin")
             }
-            GeckoError::LimitError => {
+            GeckoErrorKind::Limit => {
                 write!(f, "Uh-oh! Seems like I've reached the API limit")
             }
-            GeckoError::ParseError => {
+            GeckoErrorKind::Parse => {
                 write!(f, "Whoops! An expected parse error occured")
             }
-            GeckoError::UnknownError => {
+            GeckoErrorKind::Request => {
+                write!(f, "Couldn't reach the market data API")
+            }
+            GeckoErrorKind::Unknown => {
                 write!(f, "An unknown API error occured")
             }
+        }?;
+
+        if self.is_retryable() {
+            write!(f, ", please try again in a moment")?;
         }
-    }
-}
 
-impl From<reqwest::Error> for GeckoError {
-    fn from(_: reqwest::Error) -> Self {
-        Self::UnknownError
+        Ok(())
     }
 }
 
@@ -338,20 +455,145 @@ pub struct CoinInfo {
     pub id: String,
     pub symbol: String,
     pub name: String,
+    #[serde(default)]
+    pub market_cap_rank: Option<u32>,
 }
 
+/// A coin's market data from the coins/markets endpoint, in the currency it was asked for.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CoinMarket {
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+    pub current_price: Option<Decimal>,
+    pub price_change_percentage_1h_in_currency: Option<Decimal>,
+    pub price_change_percentage_24h_in_currency: Option<Decimal>,
+    pub price_change_percentage_7d_in_currency: Option<Decimal>,
+    pub sparkline_in_7d: Option<Sparkline>,
+}
+
+/// `(timestamp in ms, value)` series from the market_chart endpoint.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MarketChart {
+    pub prices: Vec<(f64, f64)>,
+    pub market_caps: Vec<(f64, f64)>,
+    pub total_volumes: Vec<(f64, f64)>,
+}
+
+// How far past either end of the prices a price can still be read off
+const MAX_PRICE_GAP_MS: f64 = 60.0 * 60.0 * 1000.0;
+
+impl MarketChart {
+    /// The price at `time`, interpolated between the nearest points as different coins'
+    /// series rarely share timestamps.
+    pub fn price_at(&self, time: f64) -> Option<f64> {
+        let prices = &self.prices;
+        let i = prices.partition_point(|(t, _)| *t < time);
+
+        match (i.checked_sub(1).map(|j| prices[j]), prices.get(i)) {
+            (_, Some((t, price))) if *t == time => Some(*price),
+            (Some((t0, p0)), Some((t1, p1))) => Some(p0 + (p1 - p0) * (time - t0) / (t1 - t0)),
+            (Some((t0, p0)), None) if time - t0 <= MAX_PRICE_GAP_MS => Some(p0),
+            (None, Some((t1, p1))) if t1 - time <= MAX_PRICE_GAP_MS => Some(*p1),
+            _ => None,
+        }
+    }
+}
+
+/// Prices keyed by coin id, then by lowercase currency code.
+pub type SimplePrices = HashMap<String, HashMap<String, Decimal>>;
+
 pub enum MarketChange {
     PercentageChange1h,
     PercentageChange24h,
     PercentageChange7d,
 }
 
+impl MarketChange {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MarketChange::PercentageChange1h => "1h",
+            MarketChange::PercentageChange24h => "24h",
+            MarketChange::PercentageChange7d => "7d",
+        }
+    }
+}
+
 pub enum Amount {
     CurrentPrice,
     Volume24h,
     MarketCap,
 }
 
+/// How far back a chart reaches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timeframe {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+    Max,
+}
+
+impl Timeframe {
+    pub const ALL: [Timeframe; 6] = [
+        Timeframe::Day,
+        Timeframe::Week,
+        Timeframe::Month,
+        Timeframe::Quarter,
+        Timeframe::Year,
+        Timeframe::Max,
+    ];
+
+    /// The `days` value CoinGecko expects for this timeframe.
+    pub fn days(&self) -> &'static str {
+        match self {
+            Timeframe::Day => "1",
+            Timeframe::Week => "7",
+            Timeframe::Month => "30",
+            Timeframe::Quarter => "90",
+            Timeframe::Year => "365",
+            Timeframe::Max => "max",
+        }
+    }
+
+    /// The name used for the `timeframe` command option.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timeframe::Day => "24h",
+            Timeframe::Week => "7d",
+            Timeframe::Month => "30d",
+            Timeframe::Quarter => "90d",
+            Timeframe::Year => "1y",
+            Timeframe::Max => "max",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Timeframe::Day => "1D",
+            Timeframe::Week => "7D",
+            Timeframe::Month => "30D",
+            Timeframe::Quarter => "90D",
+            Timeframe::Year => "1Y",
+            Timeframe::Max => "Max",
+        }
+    }
+
+    pub fn from_days(days: &str) -> Option<Self> {
+        Timeframe::ALL
+            .into_iter()
+            .find(|timeframe| timeframe.days() == days)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Timeframe::ALL
+            .into_iter()
+            .find(|timeframe| timeframe.name() == name)
+    }
+}
+
 const ANCHOR_REGEX: &str = r##"<a href="(.+?)">(.+?)</a>"##;
 
 impl Coin {
@@ -408,8 +650,45 @@ impl Coin {
             }
         };
 
-        let rounded = change.round_dp(1);
-        let prefix = if change.is_sign_positive() { "+" } else { "" };
-        format!("```diff\n{}{:.1}%```", prefix, rounded)
+        format!("```diff\n{}```", format_change(change))
+    }
+}
+
+impl CoinMarket {
+    pub fn get_formatted_price(&self, currency: Currency) -> String {
+        match self.current_price {
+            Some(price) => format!("```{}```", Money::from_decimal(price, &currency)),
+            None => "```No price```".into(),
+        }
     }
+
+    /// Several changes in one diff block, a line each, eg. `+2.1% 24h`.
+    pub fn get_formatted_changes(&self, market_changes: &[MarketChange]) -> String {
+        let lines: Vec<String> = market_changes
+            .iter()
+            .map(|market_change| {
+                let change = match market_change {
+                    MarketChange::PercentageChange1h => self.price_change_percentage_1h_in_currency,
+                    MarketChange::PercentageChange24h => {
+                        self.price_change_percentage_24h_in_currency
+                    }
+                    MarketChange::PercentageChange7d => self.price_change_percentage_7d_in_currency,
+                };
+
+                match change {
+                    Some(change) => format!("{} {}", format_change(change), market_change.label()),
+                    None => format!("n/a {}", market_change.label()),
+                }
+            })
+            .collect();
+
+        format!("```diff\n{}```", lines.join("\n"))
+    }
+}
+
+/// A percent change with its sign, which diff blocks colour green or red.
+fn format_change(change: Decimal) -> String {
+    let rounded = change.round_dp(1);
+    let prefix = if change.is_sign_positive() { "+" } else { "" };
+    format!("{}{:.1}%", prefix, rounded)
 }
@@ -0,0 +1,203 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+
+use rand::Rng;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, sleep_until, Instant};
+
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    static RETRY_NOTICE: UnboundedSender<()>;
+}
+
+/// Runs `request`, sending on `notice` whenever one of its API calls backs off to retry,
+/// so whoever is waiting on it can be told.
+pub async fn with_retry_notice<F: Future>(notice: UnboundedSender<()>, request: F) -> F::Output {
+    RETRY_NOTICE.scope(notice, request).await
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket that queues callers until a request may be sent.
+///
+/// Waiters are served in arrival order, and the whole queue can be paused when
+/// the API tells us to back off.
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    bucket: Mutex<Bucket>,
+    paused_until: StdMutex<Option<Instant>>,
+    retrying: AtomicUsize,
+}
+
+/// Counts a retry for as long as it is held, including when its request is dropped midway.
+struct RetryGuard<'a>(&'a AtomicUsize);
+
+impl<'a> RetryGuard<'a> {
+    fn new(retrying: &'a AtomicUsize) -> Self {
+        retrying.fetch_add(1, Ordering::Relaxed);
+        RetryGuard(retrying)
+    }
+}
+
+impl Drop for RetryGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl RateLimiter {
+    pub fn new(calls_per_minute: u32) -> Self {
+        let refill_per_sec = f64::from(calls_per_minute.max(1)) / 60.0;
+        // Allow roughly ten seconds worth of calls to burst through at once
+        let capacity = (refill_per_sec * 10.0).max(1.0);
+
+        RateLimiter {
+            capacity,
+            refill_per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+            paused_until: StdMutex::new(None),
+            retrying: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+
+        loop {
+            let paused_until = *self.paused_until.lock().unwrap();
+            if let Some(until) = paused_until {
+                if until > Instant::now() {
+                    sleep_until(until).await;
+                    continue;
+                }
+            }
+
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+            bucket.updated = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                break;
+            }
+
+            let missing = 1.0 - bucket.tokens;
+            sleep(Duration::from_secs_f64(missing / self.refill_per_sec)).await;
+        }
+    }
+
+    /// Holds back every queued request for `duration`, eg. as asked by `Retry-After`.
+    pub fn pause(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut paused_until = self.paused_until.lock().unwrap();
+
+        let extends_pause = match *paused_until {
+            Some(current) => current < until,
+            None => true,
+        };
+
+        if extends_pause {
+            *paused_until = Some(until);
+        }
+    }
+
+    /// Sleeps for a jittered, exponentially growing delay before retry number `attempt`.
+    pub async fn backoff(&self, attempt: u32, retry_after: Option<Duration>) {
+        let delay = match retry_after {
+            Some(delay) => {
+                self.pause(delay);
+                delay
+            }
+            None => {
+                let exponential = BASE_BACKOFF * 2u32.saturating_pow(attempt);
+                let capped = exponential.min(MAX_BACKOFF);
+                capped.mul_f64(rand::thread_rng().gen_range(0.5..1.0))
+            }
+        };
+
+        RETRY_NOTICE.try_with(|notice| notice.send(()).ok()).ok();
+
+        let _retrying = RetryGuard::new(&self.retrying);
+        sleep(delay).await;
+    }
+
+    /// Whether the API told us to back off and requests are being retried. Requests that
+    /// are merely queued for their turn don't count.
+    pub fn is_busy(&self) -> bool {
+        let paused = match *self.paused_until.lock().unwrap() {
+            Some(until) => until > Instant::now(),
+            None => false,
+        };
+
+        paused || self.retrying.load(Ordering::Relaxed) > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::task::Poll;
+    use tokio::sync::mpsc;
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn queued_requests_are_not_busy() {
+        let limiter = RateLimiter::new(1);
+        limiter.acquire().await;
+
+        // The bucket is empty, so this one waits for its turn
+        let queued = timeout(Duration::from_millis(50), limiter.acquire()).await;
+        assert!(queued.is_err());
+        assert!(!limiter.is_busy());
+    }
+
+    #[tokio::test]
+    async fn dropped_retries_stop_being_busy() {
+        let limiter = RateLimiter::new(60);
+
+        let mut retry = Box::pin(limiter.backoff(5, None));
+        assert!(poll_once(&mut retry).await);
+        assert!(limiter.is_busy());
+
+        drop(retry);
+        assert!(!limiter.is_busy());
+    }
+
+    #[tokio::test]
+    async fn retries_send_a_notice() {
+        let limiter = RateLimiter::new(60);
+        let (notice, mut retries) = mpsc::unbounded_channel();
+
+        let retry = timeout(Duration::from_millis(10), limiter.backoff(5, None));
+        with_retry_notice(notice, retry).await.ok();
+
+        assert_eq!(retries.recv().await, Some(()));
+        assert!(!limiter.is_busy());
+    }
+
+    /// Polls `future` once, returning whether it is still pending.
+    async fn poll_once<F: Future + Unpin>(future: &mut F) -> bool {
+        std::future::poll_fn(|cx| {
+            let pending = Pin::new(&mut *future).poll(cx).is_pending();
+            Poll::Ready(pending)
+        })
+        .await
+    }
+}
@@ -1,77 +1,327 @@
+pub mod cache;
+pub mod config;
+pub mod index;
 pub mod lib;
+pub mod limiter;
+pub mod provider;
+pub mod resolver;
+
+use std::env;
+use std::time::Duration;
+
 use anyhow::Result;
-use reqwest::StatusCode;
+use async_trait::async_trait;
+use reqwest::header::RETRY_AFTER;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Client, Response, StatusCode};
+use rusty_money::iso::Currency;
+use serde::de::DeserializeOwned;
 
-use self::lib::{CoinInfo, GeckoError};
+use self::cache::{CacheStats, CachedProvider};
+use self::config::GeckoConfig;
+use self::lib::{
+    Coin, CoinInfo, CoinMarket, GeckoError, GeckoErrorKind, GeckoErrorSource, MarketChart,
+    SimplePrices, Timeframe,
+};
+use self::limiter::RateLimiter;
+use self::provider::MarketDataProvider;
 
-pub async fn get_coin(coin: &str) -> Result<lib::Coin, GeckoError> {
-    let url = format!(
-        "https://api.coingecko.com/api/v3/coins/{}?sparkline=true",
-        coin.to_lowercase()
-    );
+const MAX_RETRIES: u32 = 3;
+// Keeps simple/price urls comfortably short
+const SIMPLE_PRICE_BATCH: usize = 100;
+// The most coins/markets returns on a page
+const MARKETS_BATCH: usize = 250;
 
-    let res = reqwest::get(url).await?;
+lazy_static! {
+    static ref PROVIDER: CachedProvider = CachedProvider::new(create_provider());
+}
 
-    match res.status() {
-        StatusCode::OK => match res.json::<lib::Coin>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(err) => {
-                println!("ERROR PARSING: {}", err);
-                Err(GeckoError::ParseError)
-            }
-        },
-        StatusCode::NOT_FOUND => Err(GeckoError::CoinNotFoundError),
-        StatusCode::UNAUTHORIZED => Err(GeckoError::LimitError),
-        _ => Err(GeckoError::UnknownError),
+fn create_provider() -> Box<dyn MarketDataProvider> {
+    let provider = env::var("MARKET_PROVIDER").unwrap_or("coingecko".into());
+
+    match provider.as_str() {
+        "coingecko" => Box::new(CoinGecko::new(GeckoConfig::from_env())),
+        other => panic!("Unknown market data provider: {}", other),
     }
 }
 
-pub async fn get_ohlc(coin: &str) -> Result<Vec<Vec<f64>>, GeckoError> {
-    let url = format!(
-        "https://api.coingecko.com/api/v3/coins/{}/ohlc?vs_currency=usd&days=7",
-        coin.to_lowercase()
-    );
+/// The market data provider selected for this deployment.
+pub fn get_provider() -> &'static dyn MarketDataProvider {
+    &*PROVIDER
+}
+
+/// Cache hits and misses across every cached endpoint since startup.
+pub fn get_cache_stats() -> CacheStats {
+    PROVIDER.stats()
+}
+
+pub struct CoinGecko {
+    client: Client,
+    base_url: String,
+    limiter: RateLimiter,
+}
+
+impl CoinGecko {
+    pub fn new(config: GeckoConfig) -> Self {
+        let mut headers = HeaderMap::new();
+
+        if let (Some(header), Some(key)) = (config.tier.key_header(), &config.api_key) {
+            let mut value = HeaderValue::from_str(key).expect("Invalid CoinGecko API key");
+            value.set_sensitive(true);
+            headers.insert(header, value);
+        }
+
+        let client = Client::builder()
+            .default_headers(headers)
+            .build()
+            .expect("Error creating CoinGecko client");
+
+        CoinGecko {
+            client,
+            base_url: config.base_url,
+            limiter: RateLimiter::new(config.calls_per_minute),
+        }
+    }
+
+    /// Requests `endpoint`, substituting `{id}` with the coin's id, and parses the JSON reply.
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        coin: Option<&str>,
+        query: &str,
+    ) -> Result<T, GeckoError> {
+        let coin_id = coin.map(|coin| coin.to_lowercase());
+        let path = endpoint.replace("{id}", coin_id.as_deref().unwrap_or_default());
+        let url = match query {
+            "" => format!("{}{}", self.base_url, path),
+            _ => format!("{}{}?{}", self.base_url, path, query),
+        };
+        let error = |kind| GeckoError::new(kind, endpoint).with_coin(coin_id.as_deref());
+        let mut attempt = 0;
 
-    let res = reqwest::get(url).await?;
+        let res = loop {
+            self.limiter.acquire().await;
+            let res = self.client.get(&url).send().await.map_err(|err| {
+                error(GeckoErrorKind::Request).with_source(GeckoErrorSource::Request(err))
+            })?;
+            let status = res.status();
 
-    match res.status() {
-        StatusCode::OK => match res.json::<Vec<Vec<f64>>>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(_) => Err(GeckoError::ParseError),
-        },
-        StatusCode::NOT_FOUND => Err(GeckoError::CoinNotFoundError),
-        StatusCode::UNAUTHORIZED => Err(GeckoError::LimitError),
-        _ => Err(GeckoError::UnknownError),
+            let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            if !retryable || attempt >= MAX_RETRIES {
+                break res;
+            }
+
+            self.limiter.backoff(attempt, get_retry_after(&res)).await;
+            attempt += 1;
+        };
+
+        let status = res.status();
+        let kind = match status {
+            StatusCode::OK => None,
+            StatusCode::NOT_FOUND => Some(GeckoErrorKind::CoinNotFound),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Some(GeckoErrorKind::Auth),
+            StatusCode::TOO_MANY_REQUESTS => Some(GeckoErrorKind::Limit),
+            _ => Some(GeckoErrorKind::Unknown),
+        };
+        if let Some(kind) = kind {
+            return Err(error(kind).with_status(status));
+        }
+
+        let body = res.bytes().await.map_err(|err| {
+            error(GeckoErrorKind::Request)
+                .with_status(status)
+                .with_source(GeckoErrorSource::Request(err))
+        })?;
+
+        let deserializer = &mut serde_json::Deserializer::from_slice(&body);
+        serde_path_to_error::deserialize(deserializer).map_err(|err| {
+            error(GeckoErrorKind::Parse)
+                .with_status(status)
+                .with_source(GeckoErrorSource::Parse(err))
+        })
     }
 }
 
-pub async fn get_list() -> Result<Vec<CoinInfo>, GeckoError> {
-    let url = "https://api.coingecko.com/api/v3/coins/list";
+fn get_retry_after(res: &Response) -> Option<Duration> {
+    let seconds = res.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[async_trait]
+impl MarketDataProvider for CoinGecko {
+    async fn get_coin(&self, coin: &str) -> Result<Coin, GeckoError> {
+        self.fetch("/coins/{id}", Some(coin), "sparkline=true")
+            .await
+    }
+
+    async fn get_ohlc(
+        &self,
+        coin: &str,
+        timeframe: Timeframe,
+        currency: Currency,
+    ) -> Result<Vec<Vec<f64>>, GeckoError> {
+        let query = format!(
+            "vs_currency={}&days={}",
+            currency.iso_alpha_code.to_lowercase(),
+            timeframe.days()
+        );
+
+        self.fetch("/coins/{id}/ohlc", Some(coin), &query).await
+    }
+
+    async fn get_market_chart(
+        &self,
+        coin: &str,
+        timeframe: Timeframe,
+        currency: Currency,
+    ) -> Result<MarketChart, GeckoError> {
+        let query = format!(
+            "vs_currency={}&days={}",
+            currency.iso_alpha_code.to_lowercase(),
+            timeframe.days()
+        );
+
+        self.fetch("/coins/{id}/market_chart", Some(coin), &query)
+            .await
+    }
+
+    async fn get_list(&self) -> Result<Vec<CoinInfo>, GeckoError> {
+        self.fetch("/coins/list", None, "").await
+    }
 
-    let res = reqwest::get(url).await?;
+    async fn get_top_coins(&self, count: u8) -> Result<Vec<CoinInfo>, GeckoError> {
+        let query = format!(
+            "vs_currency=usd&order=gecko_desc&per_page={}&page=1&sparkline=false",
+            count
+        );
 
-    match res.status() {
-        StatusCode::OK => match res.json::<Vec<CoinInfo>>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(_) => Err(GeckoError::ParseError),
-        },
-        StatusCode::NOT_FOUND => Err(GeckoError::CoinNotFoundError),
-        StatusCode::UNAUTHORIZED => Err(GeckoError::LimitError),
-        _ => Err(GeckoError::UnknownError),
+        self.fetch("/coins/markets", None, &query).await
+    }
+
+    async fn get_simple_prices(
+        &self,
+        ids: &[String],
+        currencies: &[String],
+    ) -> Result<SimplePrices, GeckoError> {
+        let mut prices = SimplePrices::new();
+
+        for batch in ids.chunks(SIMPLE_PRICE_BATCH) {
+            let query = format!(
+                "ids={}&vs_currencies={}",
+                batch.join(","),
+                currencies.join(",")
+            );
+            let batch_prices: SimplePrices = self.fetch("/simple/price", None, &query).await?;
+            prices.extend(batch_prices);
+        }
+
+        Ok(prices)
+    }
+
+    async fn get_markets(
+        &self,
+        ids: &[String],
+        currency: Currency,
+    ) -> Result<Vec<CoinMarket>, GeckoError> {
+        let mut markets = vec![];
+
+        for batch in ids.chunks(MARKETS_BATCH) {
+            let query = format!(
+                "vs_currency={}&ids={}&per_page={}&page=1&sparkline=true&price_change_percentage=1h,24h,7d",
+                currency.iso_alpha_code.to_lowercase(),
+                batch.join(","),
+                MARKETS_BATCH
+            );
+            let batch_markets: Vec<CoinMarket> = self.fetch("/coins/markets", None, &query).await?;
+            markets.extend(batch_markets);
+        }
+
+        Ok(markets)
+    }
+
+    fn is_busy(&self) -> bool {
+        self.limiter.is_busy()
     }
 }
 
-pub async fn get_top_coins(count: u8) -> Result<Vec<CoinInfo>, GeckoError> {
-    let url = format!("https://api.coingecko.com/api/v3/coins/markets?vs_currency=usd&order=gecko_desc&per_page={}&page=1&sparkline=false", count);
-    let res = reqwest::get(url).await?;
-
-    match res.status() {
-        StatusCode::OK => match res.json::<Vec<CoinInfo>>().await {
-            Ok(parsed) => Ok(parsed),
-            Err(_) => Err(GeckoError::ParseError),
-        },
-        StatusCode::NOT_FOUND => Err(GeckoError::CoinNotFoundError),
-        StatusCode::UNAUTHORIZED => Err(GeckoError::LimitError),
-        _ => Err(GeckoError::UnknownError),
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::config::ApiTier;
+    use super::*;
+
+    /// Answers a single request with `body`, handing back its request line and headers.
+    fn serve_once(body: &'static str) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request: Vec<String> = BufReader::new(stream.try_clone().unwrap())
+                .lines()
+                .map(|line| line.unwrap())
+                .take_while(|line| !line.is_empty())
+                .collect();
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+
+            request
+        });
+
+        (format!("http://{}/api/v3/", address), handle)
+    }
+
+    fn get_header<'a>(request: &'a [String], name: &str) -> Option<&'a str> {
+        request.iter().skip(1).find_map(|line| {
+            let (header, value) = line.split_once(':')?;
+            header.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    async fn get_list_request(tier: ApiTier, api_key: Option<&str>) -> Vec<String> {
+        let (base_url, server) =
+            serve_once(r#"[{"id":"bitcoin","symbol":"btc","name":"Bitcoin"}]"#);
+        let config = GeckoConfig::new(tier, api_key.map(String::from)).with_base_url(&base_url);
+
+        let list = CoinGecko::new(config).get_list().await.unwrap();
+        assert_eq!(list[0].id, "bitcoin");
+
+        server.join().unwrap()
+    }
+
+    #[tokio::test]
+    async fn pro_requests_carry_the_pro_key() {
+        let request = get_list_request(ApiTier::Pro, Some("pro-key")).await;
+
+        assert_eq!(request[0], "GET /api/v3/coins/list HTTP/1.1");
+        assert_eq!(get_header(&request, "x-cg-pro-api-key"), Some("pro-key"));
+        assert_eq!(get_header(&request, "x-cg-demo-api-key"), None);
+    }
+
+    #[tokio::test]
+    async fn demo_requests_carry_the_demo_key() {
+        let request = get_list_request(ApiTier::Demo, Some("demo-key")).await;
+
+        assert_eq!(request[0], "GET /api/v3/coins/list HTTP/1.1");
+        assert_eq!(get_header(&request, "x-cg-demo-api-key"), Some("demo-key"));
+        assert_eq!(get_header(&request, "x-cg-pro-api-key"), None);
+    }
+
+    #[tokio::test]
+    async fn public_requests_carry_no_key() {
+        let request = get_list_request(ApiTier::Public, None).await;
+
+        assert_eq!(request[0], "GET /api/v3/coins/list HTTP/1.1");
+        assert_eq!(get_header(&request, "x-cg-demo-api-key"), None);
+        assert_eq!(get_header(&request, "x-cg-pro-api-key"), None);
     }
 }
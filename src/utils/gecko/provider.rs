@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use rusty_money::iso::Currency;
+
+use super::lib::{Coin, CoinInfo, CoinMarket, GeckoError, MarketChart, SimplePrices, Timeframe};
+
+/// A source of market data for the Discord commands.
+///
+/// CoinGecko is the only implementation for now, but anything able to answer
+/// these calls can be swapped in through `MARKET_PROVIDER`.
+#[async_trait]
+pub trait MarketDataProvider: Send + Sync {
+    async fn get_coin(&self, coin: &str) -> Result<Coin, GeckoError>;
+
+    /// `[timestamp, open, high, low, close]` candles covering `timeframe`, in `currency`.
+    async fn get_ohlc(
+        &self,
+        coin: &str,
+        timeframe: Timeframe,
+        currency: Currency,
+    ) -> Result<Vec<Vec<f64>>, GeckoError>;
+
+    /// Price, market cap and volume series covering `timeframe`, in `currency`.
+    async fn get_market_chart(
+        &self,
+        coin: &str,
+        timeframe: Timeframe,
+        currency: Currency,
+    ) -> Result<MarketChart, GeckoError>;
+
+    async fn get_list(&self) -> Result<Vec<CoinInfo>, GeckoError>;
+
+    async fn get_top_coins(&self, count: u8) -> Result<Vec<CoinInfo>, GeckoError>;
+
+    /// Current prices of several coins in several currencies, in as few requests as possible.
+    async fn get_simple_prices(
+        &self,
+        ids: &[String],
+        currencies: &[String],
+    ) -> Result<SimplePrices, GeckoError>;
+
+    /// Market data and 7d sparklines of several coins, in as few requests as possible. Coins
+    /// without market data are left out.
+    async fn get_markets(
+        &self,
+        ids: &[String],
+        currency: Currency,
+    ) -> Result<Vec<CoinMarket>, GeckoError>;
+
+    /// Whether requests are currently being queued or retried because of rate limits.
+    fn is_busy(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,193 @@
+use super::index;
+use super::lib::{CoinInfo, GeckoError};
+use super::provider::MarketDataProvider;
+
+const MAX_CANDIDATES: usize = 5;
+
+pub enum Resolution {
+    Found(CoinInfo),
+    Ambiguous(Vec<CoinInfo>),
+    NotFound,
+}
+
+/// Resolves free text (an id, symbol or name) to a coin, using the coin index when it
+/// has been loaded and the provider's coin list otherwise.
+pub async fn resolve_coin(
+    provider: &dyn MarketDataProvider,
+    query: &str,
+) -> Result<Resolution, GeckoError> {
+    let snapshot = index::get_snapshot();
+    if !snapshot.is_empty() {
+        return Ok(resolve(&snapshot, query));
+    }
+
+    let list = provider.get_list().await?;
+
+    Ok(resolve(&list, query))
+}
+
+/// Tries, in order: exact id, exact symbol, case-insensitive name, then the closest
+/// ids, symbols and names within an edit distance that grows with the query's length.
+pub fn resolve(list: &[CoinInfo], query: &str) -> Resolution {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Resolution::NotFound;
+    }
+    let id_query = query.replace(' ', "-");
+
+    if let Some(coin) = list.iter().find(|coin| coin.id == id_query) {
+        return Resolution::Found(coin.clone());
+    }
+
+    let by_symbol = |coin: &CoinInfo| coin.symbol.to_lowercase() == query;
+    let by_name = |coin: &CoinInfo| coin.name.to_lowercase() == query;
+    if let Some(resolution) = find_exact(list, by_symbol).or_else(|| find_exact(list, by_name)) {
+        return resolution;
+    }
+
+    let threshold = (query.chars().count() / 4).max(1);
+    let matches: Vec<(usize, &CoinInfo)> = list
+        .iter()
+        .map(|coin| (fuzzy_distance(&query, &id_query, coin), coin))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+
+    let best = matches.iter().map(|(distance, _)| *distance).min();
+    let best_count = matches
+        .iter()
+        .filter(|(distance, _)| Some(*distance) == best)
+        .count();
+
+    match (matches.len(), best_count) {
+        (0, _) => Resolution::NotFound,
+        (_, 1) => Resolution::Found(rank(matches).remove(0)),
+        _ => Resolution::Ambiguous(rank(matches)),
+    }
+}
+
+fn find_exact(list: &[CoinInfo], is_match: impl Fn(&CoinInfo) -> bool) -> Option<Resolution> {
+    let matches: Vec<(usize, &CoinInfo)> = list
+        .iter()
+        .filter(|coin| is_match(coin))
+        .map(|coin| (0, coin))
+        .collect();
+
+    match matches.len() {
+        0 => None,
+        1 => Some(Resolution::Found(matches[0].1.clone())),
+        _ => Some(Resolution::Ambiguous(rank(matches))),
+    }
+}
+
+/// Orders candidates by distance, then market cap rank, preferring shorter (usually
+/// canonical) ids when neither tells them apart.
+fn rank(mut matches: Vec<(usize, &CoinInfo)>) -> Vec<CoinInfo> {
+    matches.sort_by_key(|(distance, coin)| {
+        let rank = coin.market_cap_rank.unwrap_or(u32::MAX);
+        (*distance, rank, coin.id.len())
+    });
+
+    matches
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(_, coin)| coin.clone())
+        .collect()
+}
+
+fn fuzzy_distance(query: &str, id_query: &str, coin: &CoinInfo) -> usize {
+    let id_distance = levenshtein(id_query, &coin.id);
+    let name_distance = levenshtein(query, &coin.name.to_lowercase());
+    let symbol_distance = levenshtein(query, &coin.symbol.to_lowercase());
+
+    id_distance.min(name_distance).min(symbol_distance)
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin(id: &str, symbol: &str, name: &str, rank: Option<u32>) -> CoinInfo {
+        CoinInfo {
+            id: id.into(),
+            symbol: symbol.into(),
+            name: name.into(),
+            market_cap_rank: rank,
+        }
+    }
+
+    fn get_list() -> Vec<CoinInfo> {
+        vec![
+            coin("bitcoin", "btc", "Bitcoin", Some(1)),
+            coin("ethereum", "eth", "Ethereum", Some(2)),
+            coin("solana", "sol", "Solana", Some(5)),
+            coin("usd-coin", "usdc", "USD Coin", Some(6)),
+            coin("bridged-usdc", "usdc", "Bridged USDC", None),
+        ]
+    }
+
+    fn get_ids(query: &str) -> Result<String, Vec<String>> {
+        match resolve(&get_list(), query) {
+            Resolution::Found(coin) => Ok(coin.id),
+            Resolution::Ambiguous(coins) => Err(coins.into_iter().map(|coin| coin.id).collect()),
+            Resolution::NotFound => Err(vec![]),
+        }
+    }
+
+    #[test]
+    fn exact_match_is_found() {
+        assert_eq!(get_ids("solana"), Ok("solana".into()));
+        assert_eq!(get_ids(" USD Coin "), Ok("usd-coin".into()));
+        assert_eq!(get_ids("ETH"), Ok("ethereum".into()));
+    }
+
+    #[test]
+    fn shared_symbol_is_ambiguous() {
+        // Ranked coins come before unranked ones
+        assert_eq!(
+            get_ids("usdc"),
+            Err(vec!["usd-coin".into(), "bridged-usdc".into()])
+        );
+    }
+
+    #[test]
+    fn typo_within_distance_is_found() {
+        assert_eq!(get_ids("etherium"), Ok("ethereum".into()));
+        assert_eq!(get_ids("bitcon"), Ok("bitcoin".into()));
+    }
+
+    #[test]
+    fn unknown_query_is_not_found() {
+        assert_eq!(get_ids("dogecoin"), Err(vec![]));
+        assert_eq!(get_ids("   "), Err(vec![]));
+        // Short queries only allow a single edit
+        assert_eq!(get_ids("xyz"), Err(vec![]));
+    }
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("solana", "solana"), 0);
+        assert_eq!(levenshtein("sol", "sold"), 1);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod alerts;
+pub mod branding;
 pub mod discord;
+pub mod export;
 pub mod gecko;
 pub mod plotter;
+pub mod portfolio;
+pub mod settings;
+pub mod store;
+pub mod watchlist;
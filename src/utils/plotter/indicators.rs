@@ -0,0 +1,448 @@
+use anyhow::{anyhow, Result};
+
+// Keeps the chart readable
+const MAX_INDICATORS: usize = 5;
+const MAX_PERIOD: usize = 200;
+
+const BOLLINGER_PERIOD: usize = 20;
+const BOLLINGER_WIDTH: f64 = 2.0;
+const RSI_PERIOD: usize = 14;
+const MACD_FAST: usize = 12;
+const MACD_SLOW: usize = 26;
+const MACD_SIGNAL: usize = 9;
+
+/// A technical indicator computed over a chart's closing prices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indicator {
+    Sma(usize),
+    Ema(usize),
+    BollingerBands,
+    Rsi(usize),
+    Macd,
+}
+
+impl Indicator {
+    /// Parses a comma separated list such as `sma20,ema50,bbands,rsi,macd`.
+    pub fn parse_list(list: &str) -> Result<Vec<Indicator>> {
+        let mut indicators: Vec<Indicator> = vec![];
+
+        for name in list.split(',').map(|name| name.trim().to_lowercase()) {
+            if name.is_empty() {
+                continue;
+            }
+
+            let indicator = Indicator::parse(&name)?;
+            if !indicators.contains(&indicator) {
+                indicators.push(indicator);
+            }
+        }
+
+        if indicators.len() > MAX_INDICATORS {
+            return Err(anyhow!(
+                "Pick at most {} indicators at a time",
+                MAX_INDICATORS
+            ));
+        }
+
+        Ok(indicators)
+    }
+
+    fn parse(name: &str) -> Result<Indicator> {
+        let digits = name
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(name.len());
+        let (kind, period) = name.split_at(digits);
+        let period = match period {
+            "" => None,
+            period => Some(
+                period
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|period| (2..=MAX_PERIOD).contains(period))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Indicator periods go from 2 to {}, got `{}`",
+                            MAX_PERIOD,
+                            name
+                        )
+                    })?,
+            ),
+        };
+
+        match (kind, period) {
+            ("sma", Some(period)) => Ok(Indicator::Sma(period)),
+            ("ema", Some(period)) => Ok(Indicator::Ema(period)),
+            ("sma", None) => Ok(Indicator::Sma(20)),
+            ("ema", None) => Ok(Indicator::Ema(20)),
+            ("bbands", None) => Ok(Indicator::BollingerBands),
+            ("rsi", period) => Ok(Indicator::Rsi(period.unwrap_or(RSI_PERIOD))),
+            ("macd", None) => Ok(Indicator::Macd),
+            _ => Err(anyhow!(
+                "Unknown indicator `{}`, try sma20, ema50, bbands, rsi or macd",
+                name
+            )),
+        }
+    }
+
+    /// The name the indicator is parsed from, eg. `sma20`.
+    pub fn name(&self) -> String {
+        match self {
+            Indicator::Sma(period) => format!("sma{}", period),
+            Indicator::Ema(period) => format!("ema{}", period),
+            Indicator::BollingerBands => "bbands".into(),
+            Indicator::Rsi(period) => format!("rsi{}", period),
+            Indicator::Macd => "macd".into(),
+        }
+    }
+
+    /// The legend entry for the indicator, eg. `SMA 20`.
+    pub fn label(&self) -> String {
+        match self {
+            Indicator::Sma(period) => format!("SMA {}", period),
+            Indicator::Ema(period) => format!("EMA {}", period),
+            Indicator::BollingerBands => {
+                format!("BB {}, {}", BOLLINGER_PERIOD, BOLLINGER_WIDTH)
+            }
+            Indicator::Rsi(period) => format!("RSI {}", period),
+            Indicator::Macd => format!("MACD {}, {}, {}", MACD_FAST, MACD_SLOW, MACD_SIGNAL),
+        }
+    }
+
+    /// Whether the indicator is drawn over the prices rather than in its own pane.
+    pub fn is_overlay(&self) -> bool {
+        matches!(
+            self,
+            Indicator::Sma(_) | Indicator::Ema(_) | Indicator::BollingerBands
+        )
+    }
+
+    /// The indicator's lines over `closes`, each aligned with `closes`.
+    pub fn compute(&self, closes: &[f64]) -> Vec<Vec<Option<f64>>> {
+        match self {
+            Indicator::Sma(period) => vec![sma(closes, *period)],
+            Indicator::Ema(period) => vec![ema(closes, *period)],
+            Indicator::BollingerBands => {
+                let bands = bollinger_bands(closes, BOLLINGER_PERIOD, BOLLINGER_WIDTH);
+                vec![
+                    bands.iter().map(|band| band.map(|b| b.lower)).collect(),
+                    bands.iter().map(|band| band.map(|b| b.middle)).collect(),
+                    bands.iter().map(|band| band.map(|b| b.upper)).collect(),
+                ]
+            }
+            Indicator::Rsi(period) => vec![rsi(closes, *period)],
+            Indicator::Macd => {
+                let values = macd(closes, MACD_FAST, MACD_SLOW, MACD_SIGNAL);
+                vec![
+                    values.iter().map(|value| value.map(|v| v.macd)).collect(),
+                    values.iter().map(|value| value.map(|v| v.signal)).collect(),
+                    values
+                        .iter()
+                        .map(|value| value.map(|v| v.histogram))
+                        .collect(),
+                ]
+            }
+        }
+    }
+}
+
+/// Simple moving average, `None` until `period` values are available.
+pub fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut averages = vec![None; values.len()];
+    let mut sum = 0.0;
+
+    for (i, value) in values.iter().enumerate() {
+        sum += value;
+        if i >= period {
+            sum -= values[i - period];
+        }
+        if i + 1 >= period {
+            averages[i] = Some(sum / period as f64);
+        }
+    }
+
+    averages
+}
+
+/// Exponential moving average, seeded with the simple average of the first `period` values.
+pub fn ema(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut averages = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return averages;
+    }
+
+    let multiplier = 2.0 / (period as f64 + 1.0);
+    let mut average = values[..period].iter().sum::<f64>() / period as f64;
+    averages[period - 1] = Some(average);
+
+    for (i, value) in values.iter().enumerate().skip(period) {
+        average = (value - average) * multiplier + average;
+        averages[i] = Some(average);
+    }
+
+    averages
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bands {
+    pub lower: f64,
+    pub middle: f64,
+    pub upper: f64,
+}
+
+/// Bollinger Bands, `width` population standard deviations around the simple moving average.
+pub fn bollinger_bands(values: &[f64], period: usize, width: f64) -> Vec<Option<Bands>> {
+    sma(values, period)
+        .into_iter()
+        .enumerate()
+        .map(|(i, middle)| {
+            let middle = middle?;
+            let window = &values[i + 1 - period..=i];
+            let variance = window
+                .iter()
+                .map(|value| (value - middle).powi(2))
+                .sum::<f64>()
+                / period as f64;
+            let deviation = variance.sqrt() * width;
+
+            Some(Bands {
+                lower: middle - deviation,
+                middle,
+                upper: middle + deviation,
+            })
+        })
+        .collect()
+}
+
+/// Relative strength index using Wilder's smoothing, from 0 to 100.
+pub fn rsi(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut indexes = vec![None; values.len()];
+    if period == 0 || values.len() <= period {
+        return indexes;
+    }
+
+    let changes: Vec<f64> = values.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let gain = |change: &f64| change.max(0.0);
+    let loss = |change: &f64| (-change).max(0.0);
+
+    let mut average_gain = changes[..period].iter().map(gain).sum::<f64>() / period as f64;
+    let mut average_loss = changes[..period].iter().map(loss).sum::<f64>() / period as f64;
+    let index = |gain: f64, loss: f64| {
+        if loss == 0.0 {
+            100.0
+        } else {
+            100.0 - 100.0 / (1.0 + gain / loss)
+        }
+    };
+    indexes[period] = Some(index(average_gain, average_loss));
+
+    for (i, change) in changes.iter().enumerate().skip(period) {
+        average_gain = (average_gain * (period - 1) as f64 + gain(change)) / period as f64;
+        average_loss = (average_loss * (period - 1) as f64 + loss(change)) / period as f64;
+        indexes[i + 1] = Some(index(average_gain, average_loss));
+    }
+
+    indexes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacdValue {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+/// Moving average convergence divergence, with its signal line and histogram.
+pub fn macd(values: &[f64], fast: usize, slow: usize, signal: usize) -> Vec<Option<MacdValue>> {
+    let fast_averages = ema(values, fast);
+    let slow_averages = ema(values, slow);
+
+    let lines: Vec<Option<f64>> = fast_averages
+        .iter()
+        .zip(slow_averages.iter())
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+
+    // The signal line only starts once the MACD line has values
+    let start = match lines.iter().position(|line| line.is_some()) {
+        Some(start) => start,
+        None => return vec![None; values.len()],
+    };
+    let defined: Vec<f64> = lines[start..].iter().flatten().copied().collect();
+    let signals = ema(&defined, signal);
+
+    let mut values_out = vec![None; values.len()];
+    for (offset, signal) in signals.into_iter().enumerate() {
+        if let Some(signal) = signal {
+            let macd = defined[offset];
+            values_out[start + offset] = Some(MacdValue {
+                macd,
+                signal,
+                histogram: macd - signal,
+            });
+        }
+    }
+
+    values_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Published tables are rounded to 2 decimals
+    const TOLERANCE: f64 = 0.01;
+    // The RSI worksheet also rounds its average gains and losses, which drifts by a few hundredths
+    const RSI_TOLERANCE: f64 = 0.1;
+
+    // StockCharts' moving average worked example
+    const AVERAGE_CLOSES: [f64; 30] = [
+        22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15, 22.39, 22.38,
+        22.61, 23.36, 24.05, 23.75, 23.83, 23.95, 23.63, 23.82, 23.87, 23.65, 23.19, 23.10, 23.33,
+        22.68, 23.10, 22.40, 22.17,
+    ];
+
+    // Wilder's 14 period RSI worked example, as published by StockCharts
+    const RSI_CLOSES: [f64; 33] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61,
+        46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78, 45.35,
+        44.03, 44.18, 44.22, 44.57, 43.42, 42.66, 43.13,
+    ];
+
+    fn assert_series(actual: &[Option<f64>], expected: &[f64], warm_up: usize, tolerance: f64) {
+        assert_eq!(actual.len(), warm_up + expected.len());
+        assert!(actual[..warm_up].iter().all(|value| value.is_none()));
+
+        for (i, (actual, expected)) in actual[warm_up..].iter().zip(expected).enumerate() {
+            let actual = actual.unwrap_or_else(|| panic!("No value at {}", warm_up + i));
+            assert!(
+                (actual - expected).abs() < tolerance,
+                "{} at {}, expected {}",
+                actual,
+                warm_up + i,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn sma_matches_reference() {
+        let expected = [
+            22.22, 22.21, 22.23, 22.26, 22.31, 22.42, 22.61, 22.77, 22.91, 23.08, 23.21, 23.38,
+            23.53, 23.65, 23.71, 23.69, 23.61, 23.51, 23.43, 23.28, 23.13,
+        ];
+
+        assert_series(&sma(&AVERAGE_CLOSES, 10), &expected, 9, TOLERANCE);
+    }
+
+    #[test]
+    fn ema_matches_reference() {
+        let expected = [
+            22.22, 22.21, 22.24, 22.27, 22.33, 22.52, 22.80, 22.97, 23.13, 23.28, 23.34, 23.43,
+            23.51, 23.54, 23.47, 23.40, 23.39, 23.26, 23.23, 23.08, 22.92,
+        ];
+
+        assert_series(&ema(&AVERAGE_CLOSES, 10), &expected, 9, TOLERANCE);
+    }
+
+    #[test]
+    fn rsi_matches_wilder() {
+        let expected = [
+            70.53, 66.32, 66.55, 69.41, 66.36, 57.97, 62.93, 63.26, 56.06, 62.38, 54.71, 50.42,
+            39.99, 41.46, 41.87, 45.46, 37.30, 33.08, 37.77,
+        ];
+
+        assert_series(&rsi(&RSI_CLOSES, 14), &expected, 14, RSI_TOLERANCE);
+    }
+
+    #[test]
+    fn rsi_is_100_without_losses() {
+        let closes: Vec<f64> = (1..=20).map(f64::from).collect();
+
+        assert_series(&rsi(&closes, 14), &[100.0; 6], 14, TOLERANCE);
+    }
+
+    #[test]
+    fn bollinger_bands_match_reference() {
+        // 1 to 20 average 10.5, with a population standard deviation of sqrt(399 / 12)
+        let closes: Vec<f64> = (1..=20).map(f64::from).collect();
+        let deviation = (399.0f64 / 12.0).sqrt();
+        let bands = bollinger_bands(&closes, 20, 2.0);
+
+        assert!(bands[..19].iter().all(|band| band.is_none()));
+        let band = bands[19].unwrap();
+        assert!((band.middle - 10.5).abs() < TOLERANCE);
+        assert!((band.upper - (10.5 + 2.0 * deviation)).abs() < TOLERANCE);
+        assert!((band.lower - (10.5 - 2.0 * deviation)).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn bollinger_bands_collapse_on_flat_prices() {
+        let bands = bollinger_bands(&[5.0; 25], 20, 2.0);
+
+        for band in bands[19..].iter() {
+            assert_eq!(
+                *band,
+                Some(Bands {
+                    lower: 5.0,
+                    middle: 5.0,
+                    upper: 5.0
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn macd_matches_reference() {
+        // On a steady ramp an EMA trails by (period - 1) / 2, so 12/26 MACD is 26 - 12 / 2 = 7
+        // from the first slow average on, and the signal line settles on it too
+        let closes: Vec<f64> = (0..60).map(f64::from).collect();
+        let values = macd(&closes, 12, 26, 9);
+
+        // The signal needs 9 MACD values, the first of which comes with the 26th close
+        assert!(values[..33].iter().all(|value| value.is_none()));
+        for value in values[33..].iter() {
+            let value = value.unwrap();
+            assert!((value.macd - 7.0).abs() < 1e-9);
+            assert!((value.signal - 7.0).abs() < 1e-9);
+            assert!(value.histogram.abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn macd_is_the_difference_of_its_averages() {
+        let closes: Vec<f64> = RSI_CLOSES.to_vec();
+        let (fast, slow) = (ema(&closes, 12), ema(&closes, 26));
+        let values = macd(&closes, 12, 26, 9);
+
+        let lines: Vec<f64> = (25..closes.len())
+            .map(|i| fast[i].unwrap() - slow[i].unwrap())
+            .collect();
+        let signals = ema(&lines, 9);
+
+        for (offset, signal) in signals.iter().enumerate() {
+            let value = values[25 + offset];
+            match signal {
+                Some(signal) => {
+                    let value = value.unwrap();
+                    assert!((value.macd - lines[offset]).abs() < 1e-9);
+                    assert!((value.signal - signal).abs() < 1e-9);
+                    assert!((value.histogram - (lines[offset] - signal)).abs() < 1e-9);
+                }
+                None => assert!(value.is_none()),
+            }
+        }
+    }
+
+    #[test]
+    fn short_series_have_no_values() {
+        let closes = [1.0, 2.0, 3.0];
+
+        assert_eq!(sma(&closes, 5), vec![None; 3]);
+        assert_eq!(ema(&closes, 5), vec![None; 3]);
+        assert_eq!(bollinger_bands(&closes, 5, 2.0), vec![None; 3]);
+        // RSI needs one more value than its period, as it works on changes
+        assert_eq!(rsi(&closes, 3), vec![None; 3]);
+        assert_eq!(rsi(&closes, 0), vec![None; 3]);
+        assert_eq!(macd(&closes, 12, 26, 9), vec![None; 3]);
+    }
+}
@@ -1,142 +1,829 @@
+pub mod indicators;
+pub mod theme;
+
+use std::f64::consts::PI;
+use std::ops::Range;
+
 use anyhow::Result;
 use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder};
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
 use plotters::{
     prelude::*,
     style::text_anchor::{HPos, Pos, VPos},
 };
-use rust_decimal::prelude::ToPrimitive;
-use rusty_money::{iso, Money};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use rusty_money::{iso::Currency, Money};
 
-use super::gecko::lib::Coin;
+use self::indicators::Indicator;
+use self::theme::Theme;
 
 const GRAPH_WIDTH: u32 = 1024;
 const GRAPH_HEIGHT: u32 = 600;
+// Heights of the panes below the price chart
+const PANE_HEIGHT: u32 = 160;
+const VOLUME_HEIGHT: u32 = 120;
+const ALLOCATION_HEIGHT: u32 = 360;
+// Smaller holdings are grouped into one "Other" slice
+const MAX_SLICES: usize = 5;
+const TABLE_HEADER_HEIGHT: u32 = 50;
+const TABLE_ROW_HEIGHT: u32 = 64;
 
-pub fn get_line_chart(coin: &Coin) -> Result<String> {
-    let data = coin.market_data.sparkline_7d.price.clone();
-    let (mut high, mut low): (&f64, &f64) = (&data[0], &data[0]);
-    let current_time = Utc::now();
-    let data_length = data.len() - 1;
+type DateChart<'a, 'b> =
+    ChartContext<'a, BitMapBackend<'b>, Cartesian2d<RangedDateTime<DateTime<Utc>>, RangedCoordf64>>;
+type Area<'b> = DrawingArea<BitMapBackend<'b>, Shift>;
+type DatePoints = Vec<(DateTime<Utc>, f64)>;
 
-    data.iter().for_each(|item| {
-        high = if item > high { item } else { high };
-        low = if item < low { item } else { low };
-    });
+/// What a chart's prices are denominated in.
+pub enum Quote<'a> {
+    Currency(&'a Currency),
+    /// Another coin, by its symbol
+    Coin(&'a str),
+}
+
+impl Quote<'_> {
+    fn format(&self, price: f64) -> String {
+        match self {
+            Quote::Currency(currency) => format_price(price, currency),
+            Quote::Coin(symbol) => format!("{} {}", format_value(price), symbol),
+        }
+    }
+}
 
-    let file_name = format!("{}_{}", coin.id, Local::now().format("%Y%m%d%H%M%S%f"));
-    let file_path = format!("{}.png", file_name);
+/// A chart encoded as a PNG, ready to be attached to a message.
+pub struct Chart {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
 
-    let root = BitMapBackend::new(&file_path, (GRAPH_WIDTH, GRAPH_HEIGHT)).into_drawing_area();
+impl Chart {
+    fn from_buffer(coin: &str, buffer: &[u8], height: u32) -> Result<Self> {
+        let mut data = vec![];
+        PngEncoder::new(&mut data).write_image(buffer, GRAPH_WIDTH, height, ColorType::Rgb8)?;
 
-    root.fill(&RGBColor(32, 34, 37).to_rgba())?;
+        Ok(Chart {
+            filename: format!("{}_{}.png", coin, Local::now().format("%Y%m%d%H%M%S%f")),
+            data,
+        })
+    }
+}
 
-    let chart_top = high * 1.05;
-    let chart_bottom = low / 1.05;
-    let chart_y_spec = chart_bottom..chart_top;
-    let chart_x_spec = 0..data.len() + 2;
-    let chart_color = if data[0] >= data[data.len() - 1] {
-        RED
-    } else {
-        GREEN
+/// Closing prices and their times, which every indicator is computed from, plus traded volumes.
+struct Series {
+    times: Vec<DateTime<Utc>>,
+    prices: Vec<f64>,
+    /// The lowest and highest prices drawn, including candle wicks
+    range: (f64, f64),
+    volumes: DatePoints,
+}
+
+impl Series {
+    /// Whether the price rose (or held) over the period ending at `time`.
+    fn is_up_at(&self, time: DateTime<Utc>, previous: DateTime<Utc>) -> bool {
+        let price_at = |time| match self.times.partition_point(|t| *t <= time) {
+            0 => self.prices[0],
+            i => self.prices[i - 1],
+        };
+
+        price_at(time) >= price_at(previous)
+    }
+}
+
+/// A pane drawn below the price chart.
+enum Pane {
+    Volume,
+    Indicator(Indicator),
+}
+
+impl Pane {
+    fn height(&self) -> u32 {
+        match self {
+            Pane::Volume => VOLUME_HEIGHT,
+            Pane::Indicator(_) => PANE_HEIGHT,
+        }
+    }
+}
+
+/// Plots `(timestamp in ms, price)` points as an area chart, with `(timestamp in ms, volume)` bars below.
+pub fn get_line_chart(
+    data: &[(f64, f64)],
+    volumes: &[(f64, f64)],
+    coin: &str,
+    quote: &Quote,
+    indicators: &[Indicator],
+    theme: &Theme,
+) -> Result<Chart> {
+    let (mut high, mut low) = (data[0].1, data[0].1);
+    data.iter().for_each(|(_, item)| {
+        high = high.max(*item);
+        low = low.min(*item);
+    });
+
+    let series = Series {
+        times: data.iter().map(|(x, _)| parse_time(*x)).collect(),
+        prices: data.iter().map(|(_, y)| *y).collect(),
+        range: (low, high),
+        volumes: get_volume_points(volumes),
     };
 
-    let mut chart = ChartBuilder::on(&root)
-        .set_label_area_size::<i32>(LabelAreaPosition::Left, 100)
-        .set_label_area_size::<i32>(LabelAreaPosition::Bottom, 40)
-        .build_cartesian_2d(chart_x_spec, chart_y_spec)?;
+    let chart_x_spec = series.times[0]..series.times[series.times.len() - 1];
+    let chart_color = match theme.accent {
+        Some(accent) => accent,
+        None if data[0].1 >= data[data.len() - 1].1 => theme.down,
+        None => theme.up,
+    };
 
-    let bold_line_style = ShapeStyle {
-        stroke_width: 1,
-        color: RGBColor(60, 60, 60).to_rgba(),
-        filled: false,
+    render(
+        coin,
+        quote,
+        chart_x_spec,
+        &series,
+        indicators,
+        theme,
+        |chart| {
+            chart.draw_series(
+                AreaSeries::new(
+                    data.iter().map(|(x, y)| (parse_time(*x), *y)),
+                    0.0,
+                    &chart_color.mix(0.2),
+                )
+                .border_style(&chart_color),
+            )?;
+            Ok(())
+        },
+    )
+}
+
+pub fn get_ohlc_chart(
+    data: &[Vec<f64>],
+    volumes: &[(f64, f64)],
+    coin: &str,
+    quote: &Quote,
+    indicators: &[Indicator],
+    theme: &Theme,
+) -> Result<Chart> {
+    let (mut high, mut low) = (data[0][2], data[0][3]);
+    data.iter().for_each(|item| {
+        high = high.max(item[2]);
+        low = low.min(item[3]);
+    });
+
+    let series = Series {
+        times: data.iter().map(|x| parse_time(x[0])).collect(),
+        prices: data.iter().map(|x| x[4]).collect(),
+        range: (low, high),
+        volumes: get_volume_points(volumes),
     };
 
-    let label_style = TextStyle {
-        color: RGBColor(255, 255, 255).to_backend_color(),
-        font: FontDesc::new(FontFamily::SansSerif, 20.0, FontStyle::Normal),
-        pos: Pos {
-            h_pos: HPos::Center,
-            v_pos: VPos::Top,
+    // Leave room for the first and last candles' bodies
+    let (first, last) = (series.times[0], series.times[series.times.len() - 1]);
+    let padding = (last - first) / 40;
+    let chart_x_spec = first - padding..last + padding;
+    // Narrower candles when there are many of them, eg. for the max timeframe
+    let candle_width = ((GRAPH_WIDTH - 100) as f64 / data.len() as f64 * 0.6).clamp(1.0, 15.0);
+
+    render(
+        coin,
+        quote,
+        chart_x_spec,
+        &series,
+        indicators,
+        theme,
+        |chart| {
+            chart.draw_series(data.iter().map(|x| {
+                CandleStick::new(
+                    parse_time(x[0]),
+                    x[1],
+                    x[2],
+                    x[3],
+                    x[4],
+                    theme.up.filled(),
+                    theme.down.filled(),
+                    candle_width as u32,
+                )
+            }))?;
+            Ok(())
         },
+    )
+}
+
+/// Lays out the price chart with its overlays, followed by the volume and a pane per remaining indicator.
+fn render<F>(
+    coin: &str,
+    quote: &Quote,
+    x_spec: Range<DateTime<Utc>>,
+    series: &Series,
+    indicators: &[Indicator],
+    theme: &Theme,
+    draw_prices: F,
+) -> Result<Chart>
+where
+    F: FnOnce(&mut DateChart<'_, '_>) -> Result<()>,
+{
+    let date_format = get_date_format(series.times[0], series.times[series.times.len() - 1]);
+
+    let overlays: Vec<(Indicator, Vec<Vec<Option<f64>>>)> = indicators
+        .iter()
+        .filter(|indicator| indicator.is_overlay())
+        .map(|indicator| (*indicator, indicator.compute(&series.prices)))
+        .collect();
+
+    let mut panes: Vec<Pane> = vec![];
+    if !series.volumes.is_empty() {
+        panes.push(Pane::Volume);
+    }
+    indicators
+        .iter()
+        .filter(|indicator| !indicator.is_overlay())
+        .for_each(|indicator| panes.push(Pane::Indicator(*indicator)));
+
+    // Overlays such as Bollinger Bands can reach past the prices themselves
+    let (mut low, mut high) = series.range;
+    overlays
+        .iter()
+        .flat_map(|(_, lines)| lines.iter().flatten().flatten())
+        .for_each(|value| {
+            high = high.max(*value);
+            low = low.min(*value);
+        });
+
+    let height = GRAPH_HEIGHT + panes.iter().map(Pane::height).sum::<u32>();
+    let mut buffer = vec![0u8; (GRAPH_WIDTH * height * 3) as usize];
+    let root = BitMapBackend::with_buffer(&mut buffer, (GRAPH_WIDTH, height)).into_drawing_area();
+
+    root.fill(&theme.background)?;
+
+    // Dates go under whichever pane ends up at the bottom
+    let (price_area, mut pane_area) = root.split_vertically(GRAPH_HEIGHT);
+    let price_dates = match panes.is_empty() {
+        true => Some(date_format),
+        false => None,
     };
 
+    let price_formatter = |y: &f64| quote.format(*y);
+    let mut chart = build_chart(
+        &price_area,
+        x_spec.clone(),
+        low / 1.05..high * 1.05,
+        price_dates,
+        &price_formatter,
+        theme,
+    )?;
+
+    draw_prices(&mut chart)?;
+
+    for (i, (indicator, lines)) in overlays.iter().enumerate() {
+        let color = theme.series_color(i);
+
+        for (j, line) in lines.iter().enumerate() {
+            let series = chart.draw_series(LineSeries::new(
+                get_points(&series.times, line),
+                color.stroke_width(2),
+            ))?;
+            // One legend entry per indicator, even when it's drawn with several lines
+            if j == 0 {
+                series
+                    .label(indicator.label())
+                    .legend(move |(x, y)| get_legend_line(x, y, color));
+            }
+        }
+    }
+
+    if !overlays.is_empty() {
+        draw_legend(&mut chart, theme)?;
+    }
+
+    for (i, pane) in panes.iter().enumerate() {
+        let (area, rest) = pane_area.split_vertically(pane.height());
+        let dates = match i == panes.len() - 1 {
+            true => Some(date_format),
+            false => None,
+        };
+
+        match pane {
+            Pane::Volume => draw_volume_pane(&area, x_spec.clone(), dates, series, theme)?,
+            Pane::Indicator(indicator) => {
+                draw_indicator_pane(&area, x_spec.clone(), dates, series, indicator, theme)?
+            }
+        }
+        pane_area = rest;
+    }
+
+    root.present()?;
+
+    drop(chart);
+    drop(price_area);
+    drop(pane_area);
+    drop(root);
+    Chart::from_buffer(coin, &buffer, height)
+}
+
+/// Draws traded volume as bars, green or red depending on how the price moved over each period.
+fn draw_volume_pane(
+    area: &Area,
+    x_spec: Range<DateTime<Utc>>,
+    date_format: Option<&str>,
+    series: &Series,
+    theme: &Theme,
+) -> Result<()> {
+    let high = series
+        .volumes
+        .iter()
+        .fold(0.0f64, |high, (_, volume)| high.max(*volume));
+
+    let formatter = |y: &f64| format_compact(*y);
+    let mut chart = build_chart(
+        area,
+        x_spec,
+        0.0..high.max(f64::EPSILON) * 1.1,
+        date_format,
+        &formatter,
+        theme,
+    )?;
+
+    let volumes = &series.volumes;
+    chart.draw_series(volumes.iter().enumerate().map(|(i, (time, volume))| {
+        // Each bar spans the period since the previous point
+        let previous = match i {
+            0 => *time - (volumes.get(1).map(|(next, _)| *next).unwrap_or(*time) - *time),
+            _ => volumes[i - 1].0,
+        };
+        let color = match series.is_up_at(*time, previous) {
+            true => theme.up,
+            false => theme.down,
+        };
+
+        Rectangle::new([(previous, 0.0), (*time, *volume)], color.mix(0.5).filled())
+    }))?;
+
     chart
-        .configure_mesh()
-        .bold_line_style(bold_line_style)
-        .disable_x_mesh()
-        .axis_style(&RGBColor(32, 34, 37))
-        .light_line_style(&RGBColor(32, 34, 37))
-        .y_label_formatter(&|y| {
-            let y_str = &y.to_string();
-            let value = Money::from_str(y_str, iso::USD).unwrap();
-            format!("{:0}", value)
-        })
-        .x_label_formatter(&|x| {
-            let hours: i64 = (data_length - x).try_into().unwrap();
-            let date = current_time - Duration::hours(hours);
-            date.format("%e %b").to_string()
+        .draw_series(std::iter::empty::<PathElement<(DateTime<Utc>, f64)>>())?
+        .label("Volume")
+        .legend(move |(x, y)| get_legend_line(x, y, theme.muted));
+
+    draw_legend(&mut chart, theme)?;
+
+    Ok(())
+}
+
+/// Draws an indicator on its own scale, such as RSI or MACD, below the price chart.
+fn draw_indicator_pane(
+    area: &Area,
+    x_spec: Range<DateTime<Utc>>,
+    date_format: Option<&str>,
+    series: &Series,
+    indicator: &Indicator,
+    theme: &Theme,
+) -> Result<()> {
+    let lines = indicator.compute(&series.prices);
+
+    let mut chart = match indicator {
+        Indicator::Rsi(_) => {
+            let formatter = |y: &f64| format!("{:.0}", y);
+            let mut chart = build_chart(
+                area,
+                x_spec.clone(),
+                0.0..100.0,
+                date_format,
+                &formatter,
+                theme,
+            )?;
+
+            // Overbought and oversold thresholds
+            for level in [30.0, 70.0] {
+                chart.draw_series(LineSeries::new(
+                    vec![(x_spec.start, level), (x_spec.end, level)],
+                    theme.muted.stroke_width(1),
+                ))?;
+            }
+
+            chart
+        }
+        _ => {
+            let (mut high, mut low) = (0.0f64, 0.0f64);
+            lines.iter().flatten().flatten().for_each(|value| {
+                high = high.max(*value);
+                low = low.min(*value);
+            });
+            let margin = (high - low).max(f64::EPSILON) * 0.1;
+
+            let formatter = |y: &f64| format_value(*y);
+            let mut chart = build_chart(
+                area,
+                x_spec,
+                low - margin..high + margin,
+                date_format,
+                &formatter,
+                theme,
+            )?;
+
+            // MACD's histogram is its last line, drawn as bars around zero
+            let histogram = get_points(&series.times, &lines[lines.len() - 1]);
+            chart.draw_series(histogram.into_iter().map(|(time, value)| {
+                let color = if value >= 0.0 { theme.up } else { theme.down };
+                Rectangle::new([(time, 0.0), (time, value)], color.mix(0.6).stroke_width(3))
+            }))?;
+
+            chart
+        }
+    };
+
+    let line_count = match indicator {
+        Indicator::Macd => 2,
+        _ => 1,
+    };
+    for (i, line) in lines.iter().take(line_count).enumerate() {
+        let color = theme.series_color(i + 2);
+        let series = chart.draw_series(LineSeries::new(
+            get_points(&series.times, line),
+            color.stroke_width(2),
+        ))?;
+
+        if i == 0 {
+            series
+                .label(indicator.label())
+                .legend(move |(x, y)| get_legend_line(x, y, color));
+        }
+    }
+
+    draw_legend(&mut chart, theme)?;
+
+    Ok(())
+}
+
+/// Plots each `(label, [(timestamp in ms, price)])` series as its percent change since its first point.
+pub fn get_comparison_chart(
+    series: &[(String, Vec<(f64, f64)>)],
+    name: &str,
+    theme: &Theme,
+) -> Result<Chart> {
+    let normalized: Vec<(&String, DatePoints)> = series
+        .iter()
+        .filter(|(_, data)| !data.is_empty())
+        .map(|(label, data)| {
+            let start = data[0].1;
+            let points = data
+                .iter()
+                .map(|(time, price)| (parse_time(*time), (price / start - 1.0) * 100.0))
+                .collect();
+            (label, points)
         })
-        .y_label_offset::<i32>(-10)
-        .x_labels(10)
-        .y_labels(8)
-        .x_label_style(label_style.clone())
-        .y_label_style(label_style)
-        .draw()?;
+        .collect();
 
-    chart.draw_series(
-        AreaSeries::new(
-            (0..).zip(data.iter()).map(|(x, y)| (x, *y)),
-            0.0,
-            &chart_color.mix(0.2),
-        )
-        .border_style(&chart_color),
+    let points = normalized.iter().flat_map(|(_, points)| points.iter());
+    let (mut from_date, mut to_date) = (Utc::now(), parse_time(0.0));
+    let (mut high, mut low) = (0.0f64, 0.0f64);
+    points.for_each(|(time, change)| {
+        from_date = from_date.min(*time);
+        to_date = to_date.max(*time);
+        high = high.max(*change);
+        low = low.min(*change);
+    });
+    let margin = (high - low).max(1.0) * 0.05;
+
+    let mut buffer = vec![0u8; (GRAPH_WIDTH * GRAPH_HEIGHT * 3) as usize];
+    let root =
+        BitMapBackend::with_buffer(&mut buffer, (GRAPH_WIDTH, GRAPH_HEIGHT)).into_drawing_area();
+
+    root.fill(&theme.background)?;
+
+    let formatter = |y: &f64| format!("{:+.0}%", y);
+    let mut chart = build_chart(
+        &root,
+        from_date..to_date,
+        low - margin..high + margin,
+        Some(get_date_format(from_date, to_date)),
+        &formatter,
+        theme,
     )?;
 
+    // Where every series starts from
+    chart.draw_series(LineSeries::new(
+        vec![(from_date, 0.0), (to_date, 0.0)],
+        theme.muted.stroke_width(1),
+    ))?;
+
+    for (i, (label, points)) in normalized.iter().enumerate() {
+        let color = theme.series_color(i);
+        let change = points[points.len() - 1].1;
+
+        chart
+            .draw_series(LineSeries::new(points.clone(), color.stroke_width(2)))?
+            .label(format!("{} ({:+.1}%)", label, change))
+            .legend(move |(x, y)| get_legend_line(x, y, color));
+    }
+
+    draw_legend(&mut chart, theme)?;
+
     root.present()?;
 
     drop(chart);
     drop(root);
-    Ok(file_path)
+    Chart::from_buffer(name, &buffer, GRAPH_HEIGHT)
 }
 
-pub fn get_ohlc_chart(data: &Vec<Vec<f64>>, coin: &str) -> Result<String> {
-    let (mut high, mut low): (&f64, &f64) = (&data[0][2], &data[0][2]);
+/// Plots a portfolio's `(timestamp in ms, value)` points, with a pie chart of its `(label, value)`
+/// allocation below. The value pane is left out when there's no history to plot.
+pub fn get_portfolio_chart(
+    allocation: &[(String, f64)],
+    values: &[(f64, f64)],
+    name: &str,
+    currency: &Currency,
+    theme: &Theme,
+) -> Result<Chart> {
+    let value_height = match values.len() >= 2 {
+        true => GRAPH_HEIGHT,
+        false => 0,
+    };
+    let height = value_height + ALLOCATION_HEIGHT;
 
-    data.iter().for_each(|item| {
-        high = if item[2] > *high { &item[2] } else { high };
-        low = if item[2] < *low { &item[2] } else { low };
-    });
+    let mut buffer = vec![0u8; (GRAPH_WIDTH * height * 3) as usize];
+    let root = BitMapBackend::with_buffer(&mut buffer, (GRAPH_WIDTH, height)).into_drawing_area();
+
+    root.fill(&theme.background)?;
+
+    let (value_area, allocation_area) = root.split_vertically(value_height);
+
+    if value_height > 0 {
+        let points: DatePoints = values
+            .iter()
+            .map(|(time, value)| (parse_time(*time), *value))
+            .collect();
+        let (from_date, to_date) = (points[0].0, points[points.len() - 1].0);
+
+        let (mut high, mut low) = (points[0].1, points[0].1);
+        points.iter().for_each(|(_, value)| {
+            high = high.max(*value);
+            low = low.min(*value);
+        });
+        let color = match points[0].1 > points[points.len() - 1].1 {
+            true => theme.down,
+            false => theme.up,
+        };
+
+        let formatter = |y: &f64| format_price(*y, currency);
+        let mut chart = build_chart(
+            &value_area,
+            from_date..to_date,
+            low / 1.05..high * 1.05,
+            Some(get_date_format(from_date, to_date)),
+            &formatter,
+            theme,
+        )?;
+
+        chart.draw_series(AreaSeries::new(points, 0.0, color.mix(0.2)).border_style(color))?;
+    }
+
+    draw_allocation_pane(&allocation_area, allocation, theme)?;
+
+    root.present()?;
+
+    drop(value_area);
+    drop(allocation_area);
+    drop(root);
+    Chart::from_buffer(name, &buffer, height)
+}
+
+/// Draws the allocation as a pie chart, with a legend of each slice's share to its right.
+fn draw_allocation_pane(area: &Area, allocation: &[(String, f64)], theme: &Theme) -> Result<()> {
+    let mut slices: Vec<(String, f64)> = allocation
+        .iter()
+        .filter(|(_, value)| *value > 0.0)
+        .cloned()
+        .collect();
+    slices.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    if slices.len() > MAX_SLICES {
+        let other = slices[MAX_SLICES - 1..]
+            .iter()
+            .map(|(_, value)| value)
+            .sum();
+        slices.truncate(MAX_SLICES - 1);
+        slices.push(("Other".into(), other));
+    }
+
+    let total: f64 = slices.iter().map(|(_, value)| value).sum();
+    if total <= 0.0 {
+        return Ok(());
+    }
+
+    let (_, height) = area.dim_in_pixel();
+    let radius = height as f64 / 2.0 - 30.0;
+    let center = (GRAPH_WIDTH as f64 / 4.0, height as f64 / 2.0);
+    let legend_x = GRAPH_WIDTH as i32 / 2;
+    let legend_y = (height as i32 - slices.len() as i32 * 40) / 2;
+    let font = FontDesc::new(FontFamily::SansSerif, 22.0, FontStyle::Normal).color(&theme.text);
+
+    // Slices go clockwise from the top
+    let mut start = -PI / 2.0;
+    for (i, (label, value)) in slices.iter().enumerate() {
+        let color = match label.as_str() {
+            "Other" => theme.muted,
+            _ => theme.series_color(i),
+        };
+        let sweep = value / total * 2.0 * PI;
+        let steps = (sweep / (2.0 * PI) * 180.0).ceil().max(1.0) as usize;
+
+        let mut points = vec![(center.0 as i32, center.1 as i32)];
+        points.extend((0..=steps).map(|step| {
+            let angle = start + sweep * step as f64 / steps as f64;
+            (
+                (center.0 + radius * angle.cos()) as i32,
+                (center.1 + radius * angle.sin()) as i32,
+            )
+        }));
+        area.draw(&Polygon::new(points, color.filled()))?;
+
+        let y = legend_y + i as i32 * 40;
+        area.draw(&Rectangle::new(
+            [(legend_x, y), (legend_x + 22, y + 22)],
+            color.filled(),
+        ))?;
+        area.draw(&Text::new(
+            format!("{}  {:.1}%", label, value / total * 100.0),
+            (legend_x + 36, y),
+            font.clone(),
+        ))?;
+
+        start += sweep;
+    }
+
+    Ok(())
+}
+
+/// A coin's line in a quote table.
+pub struct QuoteRow {
+    pub symbol: String,
+    pub name: String,
+    pub price: f64,
+    /// Percent change over the last 24h
+    pub change: Option<f64>,
+    /// Prices over the last 7 days, oldest first
+    pub sparkline: Vec<f64>,
+}
+
+/// Draws several coins as one compact table, each row with its price, 24h change and a
+/// sparkline of the last 7 days.
+pub fn get_quote_table(
+    rows: &[QuoteRow],
+    name: &str,
+    currency: &Currency,
+    theme: &Theme,
+) -> Result<Chart> {
+    let height = TABLE_HEADER_HEIGHT + rows.len() as u32 * TABLE_ROW_HEIGHT;
+
+    let mut buffer = vec![0u8; (GRAPH_WIDTH * height * 3) as usize];
+    let root = BitMapBackend::with_buffer(&mut buffer, (GRAPH_WIDTH, height)).into_drawing_area();
+
+    root.fill(&theme.background)?;
+
+    // Column edges: names from the left, numbers aligned on their right
+    let (name_x, price_x, change_x) = (30, 600, 760);
+    let (sparkline_left, sparkline_right) = (800, GRAPH_WIDTH as i32 - 30);
+    let left = Pos::new(HPos::Left, VPos::Center);
+    let right = Pos::new(HPos::Right, VPos::Center);
+
+    let header_font = FontDesc::new(FontFamily::SansSerif, 18.0, FontStyle::Normal);
+    let header_y = TABLE_HEADER_HEIGHT as i32 / 2;
+    for (label, x, pos) in [
+        ("Coin", name_x, left),
+        ("Price", price_x, right),
+        ("24h", change_x, right),
+        ("7d", sparkline_left, left),
+    ] {
+        root.draw(&Text::new(
+            label,
+            (x, header_y),
+            header_font.color(&theme.muted).pos(pos),
+        ))?;
+    }
+
+    let symbol_font = FontDesc::new(FontFamily::SansSerif, 24.0, FontStyle::Bold);
+    let name_font = FontDesc::new(FontFamily::SansSerif, 16.0, FontStyle::Normal);
+    let value_font = FontDesc::new(FontFamily::SansSerif, 24.0, FontStyle::Normal);
+
+    for (i, row) in rows.iter().enumerate() {
+        let top = (TABLE_HEADER_HEIGHT + i as u32 * TABLE_ROW_HEIGHT) as i32;
+        let middle = top + TABLE_ROW_HEIGHT as i32 / 2;
 
-    let (from_date, to_date) = (
-        parse_time(data[0][0]) - Duration::hours(8),
-        parse_time(data[data.len() - 1][0]) + Duration::hours(8),
-    );
+        root.draw(&PathElement::new(
+            vec![(0, top), (GRAPH_WIDTH as i32, top)],
+            theme.grid,
+        ))?;
 
-    let file_name = format!("{}_{}", coin, Local::now().format("%Y%m%d%H%M%S%f"));
-    let file_path = format!("{}.png", file_name);
+        root.draw(&Text::new(
+            row.symbol.clone(),
+            (name_x, middle - 10),
+            symbol_font.color(&theme.text).pos(left),
+        ))?;
+        root.draw(&Text::new(
+            row.name.clone(),
+            (name_x, middle + 16),
+            name_font.color(&theme.muted).pos(left),
+        ))?;
+        root.draw(&Text::new(
+            format_quote_price(row.price, currency),
+            (price_x, middle),
+            value_font.color(&theme.text).pos(right),
+        ))?;
 
-    let root = BitMapBackend::new(&file_path, (GRAPH_WIDTH, GRAPH_HEIGHT)).into_drawing_area();
+        let (change, change_color) = match row.change {
+            Some(change) if change < 0.0 => (format!("{:.1}%", change), theme.down),
+            Some(change) => (format!("+{:.1}%", change), theme.up),
+            None => ("-".into(), theme.muted),
+        };
+        root.draw(&Text::new(
+            change,
+            (change_x, middle),
+            value_font.color(&change_color).pos(right),
+        ))?;
 
-    root.fill(&RGBColor(32, 34, 37).to_rgba())?;
+        draw_sparkline(
+            &root,
+            &row.sparkline,
+            (sparkline_left, top + 12),
+            (sparkline_right, top + TABLE_ROW_HEIGHT as i32 - 12),
+            theme,
+        )?;
+    }
 
-    let chart_top = high * 1.05;
-    let chart_bottom = low / 1.05;
-    let chart_y_spec = chart_bottom..chart_top;
-    let chart_x_spec = from_date..to_date;
+    root.present()?;
+
+    drop(root);
+    Chart::from_buffer(name, &buffer, height)
+}
+
+/// Draws `prices` as a line filling the box between `from` and `to`, green when they went up.
+fn draw_sparkline(
+    area: &Area,
+    prices: &[f64],
+    from: (i32, i32),
+    to: (i32, i32),
+    theme: &Theme,
+) -> Result<()> {
+    if prices.len() < 2 {
+        return Ok(());
+    }
+
+    let (low, high) = prices
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(low, high), price| {
+            (low.min(*price), high.max(*price))
+        });
+    let span = match high > low {
+        true => high - low,
+        false => 1.0,
+    };
+    let (width, height) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+    let color = match prices[0] > prices[prices.len() - 1] {
+        true => theme.down,
+        false => theme.up,
+    };
+
+    let points: Vec<(i32, i32)> = prices
+        .iter()
+        .enumerate()
+        .map(|(i, price)| {
+            let x = from.0 as f64 + width * i as f64 / (prices.len() - 1) as f64;
+            let y = to.1 as f64 - height * (price - low) / span;
+            (x as i32, y as i32)
+        })
+        .collect();
+    area.draw(&PathElement::new(points, color.stroke_width(2)))?;
+
+    Ok(())
+}
 
-    let mut chart = ChartBuilder::on(&root)
+/// Sets up a chart with the shared styling, only showing dates when `date_format` is given.
+fn build_chart<'a, 'b>(
+    area: &'a Area<'b>,
+    x_spec: Range<DateTime<Utc>>,
+    y_spec: Range<f64>,
+    date_format: Option<&str>,
+    y_formatter: &dyn Fn(&f64) -> String,
+    theme: &Theme,
+) -> Result<DateChart<'a, 'b>> {
+    let bottom_size = match date_format {
+        Some(_) => 40,
+        None => 0,
+    };
+
+    let mut chart = ChartBuilder::on(area)
         .set_label_area_size::<i32>(LabelAreaPosition::Left, 100)
-        .set_label_area_size::<i32>(LabelAreaPosition::Bottom, 40)
-        .build_cartesian_2d(chart_x_spec, chart_y_spec)?;
+        .set_label_area_size::<i32>(LabelAreaPosition::Bottom, bottom_size)
+        .build_cartesian_2d(x_spec, y_spec)?;
 
     let bold_line_style = ShapeStyle {
         stroke_width: 1,
-        color: RGBColor(60, 60, 60).to_rgba(),
-        filled: true,
+        color: theme.grid.to_rgba(),
+        filled: false,
     };
 
     let label_style = TextStyle {
-        color: RGBColor(255, 255, 255).to_backend_color(),
+        color: theme.text.to_backend_color(),
         font: FontDesc::new(FontFamily::SansSerif, 20.0, FontStyle::Normal),
         pos: Pos {
             h_pos: HPos::Center,
@@ -144,18 +831,15 @@ pub fn get_ohlc_chart(data: &Vec<Vec<f64>>, coin: &str) -> Result<String> {
         },
     };
 
+    let date_format = date_format.unwrap_or_default();
     chart
         .configure_mesh()
         .bold_line_style(bold_line_style)
         .disable_x_mesh()
-        .axis_style(&RGBColor(32, 34, 37))
-        .light_line_style(&RGBColor(32, 34, 37))
-        .y_label_formatter(&|y| {
-            let y_str = &y.to_string();
-            let value = Money::from_str(y_str, iso::USD).unwrap();
-            format!("{:0}", value)
-        })
-        .x_label_formatter(&|x| x.format("%e %b").to_string())
+        .axis_style(&theme.background)
+        .light_line_style(&theme.background)
+        .y_label_formatter(y_formatter)
+        .x_label_formatter(&|x| x.format(date_format).to_string())
         .y_label_offset::<i32>(-10)
         .x_labels(10)
         .y_labels(8)
@@ -163,24 +847,92 @@ pub fn get_ohlc_chart(data: &Vec<Vec<f64>>, coin: &str) -> Result<String> {
         .y_label_style(label_style)
         .draw()?;
 
-    chart.draw_series(data.iter().map(|x| {
-        CandleStick::new(
-            parse_time(x[0]),
-            x[1],
-            x[2],
-            x[3],
-            x[4],
-            GREEN.filled(),
-            RED.filled(),
-            15,
+    Ok(chart)
+}
+
+fn draw_legend<'a, 'b: 'a>(chart: &mut DateChart<'a, 'b>, theme: &Theme) -> Result<()> {
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(&theme.background.mix(0.8))
+        .border_style(&theme.grid)
+        .label_font(
+            FontDesc::new(FontFamily::SansSerif, 16.0, FontStyle::Normal).color(&theme.text),
         )
-    }))?;
+        .draw()?;
 
-    root.present()?;
+    Ok(())
+}
 
-    drop(chart);
-    drop(root);
-    Ok(file_path)
+fn get_legend_line(x: i32, y: i32, color: RGBColor) -> PathElement<(i32, i32)> {
+    PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+}
+
+fn get_volume_points(volumes: &[(f64, f64)]) -> DatePoints {
+    volumes
+        .iter()
+        .map(|(time, volume)| (parse_time(*time), *volume))
+        .collect()
+}
+
+/// Pairs an indicator line with its times, skipping the warm-up period.
+fn get_points(times: &[DateTime<Utc>], line: &[Option<f64>]) -> DatePoints {
+    times
+        .iter()
+        .zip(line.iter())
+        .filter_map(|(time, value)| Some((*time, (*value)?)))
+        .collect()
+}
+
+fn format_price(price: f64, currency: &Currency) -> String {
+    let value = Money::from_decimal(Decimal::from_f64(price).unwrap_or_default(), currency);
+    format!("{:0}", value)
+}
+
+/// A price with enough digits to tell small coins apart, eg. $1,543.20 or $0.00000123.
+fn format_quote_price(price: f64, currency: &Currency) -> String {
+    if price.abs() < 1.0 {
+        return format!("{}{}", currency.symbol, format_value(price));
+    }
+
+    let mut value = Decimal::from_f64(price).unwrap_or_default().round_dp(2);
+    value.rescale(2);
+    Money::from_decimal(value, currency).to_string()
+}
+
+/// A short label for large amounts, such as 1.2B.
+fn format_compact(value: f64) -> String {
+    let units = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")];
+
+    match units.iter().find(|(size, _)| value.abs() >= *size) {
+        Some((size, unit)) => format!("{:.1}{}", value / size, unit),
+        None => format!("{:.0}", value),
+    }
+}
+
+/// A plain number with a few significant digits, however small.
+pub fn format_value(value: f64) -> String {
+    let magnitude = value.abs();
+    let decimals = if magnitude >= 1.0 || magnitude == 0.0 {
+        2
+    } else {
+        (2 - magnitude.log10().floor() as i32).min(10) as usize
+    };
+
+    format!("{:.*}", decimals, value)
+}
+
+/// Hours for a day or so of data, dates up to a few months, months beyond that.
+fn get_date_format(from: DateTime<Utc>, to: DateTime<Utc>) -> &'static str {
+    let span = to - from;
+
+    if span <= Duration::days(2) {
+        "%H:%M"
+    } else if span <= Duration::days(120) {
+        "%e %b"
+    } else {
+        "%b %Y"
+    }
 }
 
 fn parse_time(timestamp: f64) -> DateTime<Utc> {
@@ -0,0 +1,136 @@
+use plotters::style::RGBColor;
+use serde::{Deserialize, Serialize};
+
+// Accents closer than this to the background (in perceived brightness) are hard to see
+const MIN_ACCENT_CONTRAST: f64 = 60.0;
+
+/// The colour scheme charts are drawn with, chosen per guild.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Palette {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Palette::Dark, Palette::Light, Palette::HighContrast];
+
+    /// The option value, eg. `high-contrast`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Dark => "dark",
+            Palette::Light => "light",
+            Palette::HighContrast => "high-contrast",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Palette::Dark => "Dark",
+            Palette::Light => "Light",
+            Palette::HighContrast => "High contrast",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Palette::ALL
+            .iter()
+            .find(|palette| palette.name() == name)
+            .copied()
+    }
+}
+
+/// Colours for every part of a chart.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub background: RGBColor,
+    pub text: RGBColor,
+    /// Horizontal grid lines and legend borders
+    pub grid: RGBColor,
+    /// Reference lines, such as RSI thresholds
+    pub muted: RGBColor,
+    pub up: RGBColor,
+    pub down: RGBColor,
+    /// Overlays, indicator lines and compared coins, in order
+    pub series: [RGBColor; 5],
+    /// Replaces the up or down colour of line charts, eg. with the coin's brand colour
+    pub accent: Option<RGBColor>,
+}
+
+impl Theme {
+    pub fn new(palette: Palette) -> Self {
+        match palette {
+            Palette::Dark => Theme {
+                background: RGBColor(32, 34, 37),
+                text: RGBColor(255, 255, 255),
+                grid: RGBColor(60, 60, 60),
+                muted: RGBColor(90, 90, 90),
+                up: RGBColor(0, 255, 0),
+                down: RGBColor(255, 0, 0),
+                series: [
+                    RGBColor(66, 165, 245),
+                    RGBColor(255, 167, 38),
+                    RGBColor(171, 71, 188),
+                    RGBColor(38, 198, 218),
+                    RGBColor(236, 64, 122),
+                ],
+                accent: None,
+            },
+            Palette::Light => Theme {
+                background: RGBColor(255, 255, 255),
+                text: RGBColor(32, 34, 37),
+                grid: RGBColor(222, 222, 222),
+                muted: RGBColor(160, 160, 160),
+                up: RGBColor(22, 163, 74),
+                down: RGBColor(220, 38, 38),
+                series: [
+                    RGBColor(25, 118, 210),
+                    RGBColor(239, 108, 0),
+                    RGBColor(123, 31, 162),
+                    RGBColor(0, 131, 143),
+                    RGBColor(194, 24, 91),
+                ],
+                accent: None,
+            },
+            Palette::HighContrast => Theme {
+                background: RGBColor(0, 0, 0),
+                text: RGBColor(255, 255, 255),
+                grid: RGBColor(110, 110, 110),
+                muted: RGBColor(200, 200, 200),
+                up: RGBColor(0, 255, 0),
+                down: RGBColor(255, 64, 64),
+                series: [
+                    RGBColor(255, 255, 0),
+                    RGBColor(0, 255, 255),
+                    RGBColor(255, 0, 255),
+                    RGBColor(255, 255, 255),
+                    RGBColor(255, 140, 0),
+                ],
+                accent: None,
+            },
+        }
+    }
+
+    /// Uses `accent` for line charts, unless it would blend into the background.
+    pub fn with_accent(self, accent: (u8, u8, u8)) -> Self {
+        let accent = RGBColor(accent.0, accent.1, accent.2);
+
+        match (brightness(accent) - brightness(self.background)).abs() >= MIN_ACCENT_CONTRAST {
+            true => Theme {
+                accent: Some(accent),
+                ..self
+            },
+            false => self,
+        }
+    }
+
+    pub fn series_color(&self, i: usize) -> RGBColor {
+        self.series[i % self.series.len()]
+    }
+}
+
+/// Perceived brightness from 0 to 255.
+fn brightness(color: RGBColor) -> f64 {
+    0.299 * color.0 as f64 + 0.587 * color.1 as f64 + 0.114 * color.2 as f64
+}
@@ -0,0 +1,318 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use super::lib::{Transaction, TransactionKind};
+
+/// Which lots a sale or transfer out draws from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CostMethod {
+    /// Oldest lots first
+    Fifo,
+    /// Newest lots first
+    Lifo,
+    /// Every purchase is pooled into one lot at the average price
+    Average,
+}
+
+impl CostMethod {
+    pub const ALL: [CostMethod; 3] = [CostMethod::Fifo, CostMethod::Lifo, CostMethod::Average];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CostMethod::Fifo => "fifo",
+            CostMethod::Lifo => "lifo",
+            CostMethod::Average => "average",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CostMethod::Fifo => "FIFO",
+            CostMethod::Lifo => "LIFO",
+            CostMethod::Average => "Average cost",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        CostMethod::ALL
+            .iter()
+            .find(|method| method.name() == name)
+            .copied()
+    }
+}
+
+/// Coins acquired together, at the same price per coin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lot {
+    pub amount: Decimal,
+    pub price: Decimal,
+}
+
+/// The open lots of a coin and the profit already taken on it.
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    pub lots: VecDeque<Lot>,
+    pub realized: Decimal,
+}
+
+impl Book {
+    pub fn amount(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.amount).sum()
+    }
+
+    /// What the open lots cost, ie. their cost basis.
+    pub fn cost(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.amount * lot.price).sum()
+    }
+
+    /// Profit the open lots would make if sold at `price`.
+    pub fn unrealized(&self, price: Decimal) -> Decimal {
+        self.lots
+            .iter()
+            .map(|lot| lot.amount * (price - lot.price))
+            .sum()
+    }
+
+    fn open(&mut self, lot: Lot, method: CostMethod) {
+        match (method, self.lots.front_mut()) {
+            (CostMethod::Average, Some(pool)) => {
+                let amount = pool.amount + lot.amount;
+                if !amount.is_zero() {
+                    pool.price = (pool.amount * pool.price + lot.amount * lot.price) / amount;
+                }
+                pool.amount = amount;
+            }
+            _ => self.lots.push_back(lot),
+        }
+    }
+
+    /// Takes `amount` out of the lots in the order `method` dictates, returning the parts taken.
+    /// Nothing is taken when the lots don't add up to `amount`.
+    fn close(&mut self, amount: Decimal, method: CostMethod) -> Option<Vec<Lot>> {
+        if amount > self.amount() {
+            return None;
+        }
+
+        let mut remaining = amount;
+        let mut taken = vec![];
+
+        while remaining > Decimal::ZERO {
+            let lot = match method {
+                CostMethod::Lifo => self.lots.back_mut()?,
+                CostMethod::Fifo | CostMethod::Average => self.lots.front_mut()?,
+            };
+            let portion = remaining.min(lot.amount);
+
+            taken.push(Lot {
+                amount: portion,
+                price: lot.price,
+            });
+            lot.amount -= portion;
+            remaining -= portion;
+
+            if lot.amount.is_zero() {
+                match method {
+                    CostMethod::Lifo => self.lots.pop_back(),
+                    CostMethod::Fifo | CostMethod::Average => self.lots.pop_front(),
+                };
+            }
+        }
+
+        Some(taken)
+    }
+}
+
+/// Replays `transactions` in the order they happened, giving each coin's open lots and
+/// realized profit. Fails on the first transaction that takes out more than is held.
+pub fn replay(transactions: &[Transaction], method: CostMethod) -> Result<BTreeMap<String, Book>> {
+    let mut ordered: Vec<&Transaction> = transactions.iter().collect();
+    ordered.sort_by_key(|tx| (tx.time, tx.id));
+
+    let mut books: BTreeMap<String, Book> = BTreeMap::new();
+
+    for tx in ordered {
+        let book = books.entry(tx.coin.clone()).or_default();
+
+        match tx.kind {
+            TransactionKind::Buy | TransactionKind::TransferIn => book.open(
+                Lot {
+                    amount: tx.amount,
+                    price: tx.price,
+                },
+                method,
+            ),
+            TransactionKind::Sell | TransactionKind::TransferOut => {
+                let taken = book.close(tx.amount, method).ok_or_else(|| {
+                    anyhow!(
+                        "Transaction #{} takes out {} {}, but only {} is held by then",
+                        tx.id,
+                        tx.amount.normalize(),
+                        tx.coin,
+                        book.amount().normalize()
+                    )
+                })?;
+
+                // Transfers move coins at their cost, only sales realize a profit
+                if tx.kind == TransactionKind::Sell {
+                    book.realized += taken
+                        .iter()
+                        .map(|lot| lot.amount * (tx.price - lot.price))
+                        .sum::<Decimal>();
+                }
+            }
+        }
+    }
+
+    Ok(books)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(id: u64, kind: TransactionKind, amount: i64, price: i64, time: i64) -> Transaction {
+        Transaction {
+            id,
+            kind,
+            coin: "bitcoin".into(),
+            amount: Decimal::from(amount),
+            price: Decimal::from(price),
+            time,
+        }
+    }
+
+    fn lots(book: &Book) -> Vec<(i64, i64)> {
+        book.lots
+            .iter()
+            .map(|lot| {
+                (
+                    lot.amount.try_into().unwrap(),
+                    lot.price.try_into().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    // 1 @ 100, 2 @ 200 and 3 @ 300, then 4 sold @ 400
+    fn ledger() -> Vec<Transaction> {
+        vec![
+            tx(1, TransactionKind::Buy, 1, 100, 10),
+            tx(2, TransactionKind::Buy, 2, 200, 20),
+            tx(3, TransactionKind::Buy, 3, 300, 30),
+            tx(4, TransactionKind::Sell, 4, 400, 40),
+        ]
+    }
+
+    #[test]
+    fn fifo_sells_the_oldest_lots_first() {
+        let books = replay(&ledger(), CostMethod::Fifo).unwrap();
+        let book = &books["bitcoin"];
+
+        // The first two lots are used up, the third is left with 2 of its 3 coins
+        assert_eq!(lots(book), vec![(2, 300)]);
+        assert_eq!(book.cost(), Decimal::from(600));
+    }
+
+    #[test]
+    fn lifo_sells_the_newest_lots_first() {
+        let books = replay(&ledger(), CostMethod::Lifo).unwrap();
+        let book = &books["bitcoin"];
+
+        // The third lot is used up, the second is left with 1 of its 2 coins
+        assert_eq!(lots(book), vec![(1, 100), (1, 200)]);
+        assert_eq!(book.cost(), Decimal::from(300));
+    }
+
+    #[test]
+    fn average_pools_purchases_between_sales() {
+        let transactions = vec![
+            tx(1, TransactionKind::Buy, 2, 100, 10),
+            tx(2, TransactionKind::Buy, 2, 300, 20),
+            tx(3, TransactionKind::Sell, 2, 500, 30),
+            tx(4, TransactionKind::Buy, 4, 500, 40),
+            tx(5, TransactionKind::Sell, 3, 100, 50),
+        ];
+        let books = replay(&transactions, CostMethod::Average).unwrap();
+        let book = &books["bitcoin"];
+
+        // 2 left @ 200 are pooled with 4 @ 500 into 6 @ 400, and a sale keeps the pool's price
+        assert_eq!(lots(book), vec![(3, 400)]);
+        // 2 * (500 - 200) + 3 * (100 - 400)
+        assert_eq!(book.realized, Decimal::from(-300));
+    }
+
+    #[test]
+    fn realized_profit_depends_on_the_method() {
+        let realized = |method| replay(&ledger(), method).unwrap()["bitcoin"].realized;
+
+        // 1 * 300 + 2 * 200 + 1 * 100
+        assert_eq!(realized(CostMethod::Fifo), Decimal::from(800));
+        // 3 * 100 + 1 * 200
+        assert_eq!(realized(CostMethod::Lifo), Decimal::from(500));
+        // 4 coins at an average of 1400 / 6
+        let average = Decimal::from(1400) / Decimal::from(6);
+        assert_eq!(
+            realized(CostMethod::Average),
+            Decimal::from(4) * (Decimal::from(400) - average)
+        );
+    }
+
+    #[test]
+    fn overselling_fails() {
+        let mut transactions = ledger();
+        transactions.push(tx(5, TransactionKind::Sell, 3, 400, 50));
+
+        for method in CostMethod::ALL {
+            let err = replay(&transactions, method).unwrap_err();
+            assert!(err.to_string().contains("#5"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn transfers_move_coins_at_cost() {
+        let transactions = vec![
+            tx(1, TransactionKind::Buy, 2, 100, 10),
+            tx(2, TransactionKind::TransferIn, 1, 400, 20),
+            tx(3, TransactionKind::TransferOut, 2, 0, 30),
+        ];
+        let books = replay(&transactions, CostMethod::Fifo).unwrap();
+        let book = &books["bitcoin"];
+
+        // Transfers realize nothing, whatever price they were recorded at
+        assert_eq!(book.realized, Decimal::ZERO);
+        assert_eq!(lots(book), vec![(1, 400)]);
+    }
+
+    #[test]
+    fn same_time_transactions_replay_by_id() {
+        // Listed out of order, but the purchase was recorded first so the sale is covered
+        let transactions = vec![
+            tx(2, TransactionKind::Sell, 1, 200, 10),
+            tx(1, TransactionKind::Buy, 1, 100, 10),
+        ];
+        let books = replay(&transactions, CostMethod::Fifo).unwrap();
+        assert_eq!(books["bitcoin"].realized, Decimal::from(100));
+
+        // The other way around the sale comes first, with nothing to sell
+        let transactions = vec![
+            tx(1, TransactionKind::Sell, 1, 200, 10),
+            tx(2, TransactionKind::Buy, 1, 100, 10),
+        ];
+        assert!(replay(&transactions, CostMethod::Fifo).is_err());
+    }
+
+    #[test]
+    fn coins_are_kept_apart() {
+        let mut transactions = ledger();
+        transactions.push(Transaction {
+            coin: "ethereum".into(),
+            ..tx(5, TransactionKind::Buy, 1, 10, 50)
+        });
+        let books = replay(&transactions, CostMethod::Fifo).unwrap();
+
+        assert_eq!(books.len(), 2);
+        assert_eq!(lots(&books["ethereum"]), vec![(1, 10)]);
+    }
+}
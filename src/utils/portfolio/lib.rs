@@ -0,0 +1,252 @@
+use anyhow::Result;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::ledger::{replay, Book, CostMethod};
+use super::sheet::Row;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+    TransferIn,
+    TransferOut,
+}
+
+impl TransactionKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransactionKind::Buy => "Buy",
+            TransactionKind::Sell => "Sell",
+            TransactionKind::TransferIn => "Transfer in",
+            TransactionKind::TransferOut => "Transfer out",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Transaction {
+    pub id: u64,
+    pub kind: TransactionKind,
+    pub coin: String,
+    pub amount: Decimal,
+    /// Price per coin, in the portfolio's currency. For transfers in it's their cost basis,
+    /// transfers out don't use it
+    pub price: Decimal,
+    /// Unix timestamp in seconds
+    pub time: i64,
+}
+
+/// What is held of a coin, worked out from the transactions.
+#[derive(Debug, Clone)]
+pub struct Holding {
+    pub coin: String,
+    pub amount: Decimal,
+    /// What the held coins cost, in the portfolio's currency
+    pub cost: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Portfolio {
+    /// Lowercase ISO code, as used by CoinGecko. Set by the first transaction
+    pub currency: String,
+    pub next_id: u64,
+    pub transactions: Vec<Transaction>,
+}
+
+impl Holding {
+    pub fn average_price(&self) -> Decimal {
+        match self.amount.is_zero() {
+            true => Decimal::ZERO,
+            false => self.cost / self.amount,
+        }
+    }
+}
+
+impl Portfolio {
+    /// Each coin's open lots and realized profit under `method`.
+    pub fn get_books(&self, method: CostMethod) -> Result<Vec<(String, Book)>> {
+        Ok(replay(&self.transactions, method)?.into_iter().collect())
+    }
+
+    /// Every coin still held, with its cost basis under `method`.
+    pub fn get_holdings(&self, method: CostMethod) -> Result<Vec<Holding>> {
+        Ok(self
+            .get_books(method)?
+            .into_iter()
+            .filter(|(_, book)| !book.lots.is_empty())
+            .map(|(coin, book)| Holding {
+                coin,
+                amount: book.amount(),
+                cost: book.cost(),
+            })
+            .collect())
+    }
+
+    /// Records a transaction, unless it would leave a sale or transfer taking out more than
+    /// was held at the time.
+    pub fn record(
+        &mut self,
+        kind: TransactionKind,
+        coin: &str,
+        amount: Decimal,
+        price: Decimal,
+        time: i64,
+    ) -> Result<Transaction> {
+        let tx = Transaction {
+            id: self.next_id + 1,
+            kind,
+            coin: coin.into(),
+            amount,
+            price,
+            time,
+        };
+        self.transactions.push(tx.clone());
+
+        if let Err(err) = replay(&self.transactions, CostMethod::Fifo) {
+            self.transactions.pop();
+            return Err(err);
+        }
+
+        self.next_id = tx.id;
+        Ok(tx)
+    }
+
+    /// Records every row, their coins already resolved to ids, or none of them when the
+    /// ledger wouldn't add up afterwards.
+    pub fn import(&mut self, rows: Vec<Row>) -> Result<usize> {
+        let count = rows.len();
+        let transactions = rows
+            .into_iter()
+            .zip(self.next_id + 1..)
+            .map(|(row, id)| Transaction {
+                id,
+                kind: row.kind,
+                coin: row.coin,
+                amount: row.amount,
+                price: row.price,
+                time: row.time,
+            });
+        self.transactions.extend(transactions);
+
+        if let Err(err) = replay(&self.transactions, CostMethod::Fifo) {
+            self.transactions.truncate(self.transactions.len() - count);
+            return Err(err);
+        }
+
+        self.next_id += count as u64;
+        Ok(count)
+    }
+
+    /// Deletes a transaction, returning whether it existed. Fails when the ledger wouldn't
+    /// add up without it, eg. for a purchase that was later sold.
+    pub fn delete(&mut self, id: u64) -> Result<bool> {
+        let index = match self.transactions.iter().position(|tx| tx.id == id) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let tx = self.transactions.remove(index);
+
+        if let Err(err) = replay(&self.transactions, CostMethod::Fifo) {
+            self.transactions.insert(index, tx);
+            return Err(err);
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portfolio() -> Portfolio {
+        let mut portfolio = Portfolio::default();
+        portfolio
+            .record(
+                TransactionKind::Buy,
+                "bitcoin",
+                Decimal::ONE,
+                Decimal::from(100),
+                10,
+            )
+            .unwrap();
+        portfolio
+            .record(
+                TransactionKind::Sell,
+                "bitcoin",
+                Decimal::ONE,
+                Decimal::from(200),
+                20,
+            )
+            .unwrap();
+        portfolio
+    }
+
+    fn ids(portfolio: &Portfolio) -> Vec<u64> {
+        portfolio.transactions.iter().map(|tx| tx.id).collect()
+    }
+
+    #[test]
+    fn record_rejects_overselling() {
+        let mut portfolio = portfolio();
+        let sale = portfolio.record(
+            TransactionKind::Sell,
+            "bitcoin",
+            Decimal::ONE,
+            Decimal::from(200),
+            30,
+        );
+
+        assert!(sale.is_err());
+        assert_eq!(ids(&portfolio), vec![1, 2]);
+        assert_eq!(portfolio.next_id, 2);
+    }
+
+    #[test]
+    fn record_rejects_backdated_sales() {
+        let mut portfolio = portfolio();
+        // Before the purchase nothing is held yet
+        let sale = portfolio.record(
+            TransactionKind::Sell,
+            "bitcoin",
+            Decimal::ONE,
+            Decimal::from(200),
+            5,
+        );
+
+        assert!(sale.is_err());
+        assert_eq!(ids(&portfolio), vec![1, 2]);
+    }
+
+    #[test]
+    fn delete_keeps_purchases_that_were_sold() {
+        let mut portfolio = portfolio();
+
+        assert!(portfolio.delete(1).is_err());
+        assert_eq!(ids(&portfolio), vec![1, 2]);
+
+        // The sale can go, and then the purchase
+        assert!(portfolio.delete(2).unwrap());
+        assert!(portfolio.delete(1).unwrap());
+        assert!(!portfolio.delete(1).unwrap());
+        assert!(portfolio.transactions.is_empty());
+    }
+
+    #[test]
+    fn ids_are_not_reused() {
+        let mut portfolio = portfolio();
+        portfolio.delete(2).unwrap();
+        let tx = portfolio
+            .record(
+                TransactionKind::Buy,
+                "bitcoin",
+                Decimal::ONE,
+                Decimal::from(100),
+                30,
+            )
+            .unwrap();
+
+        assert_eq!(tx.id, 3);
+    }
+}
@@ -0,0 +1,89 @@
+pub mod ledger;
+pub mod lib;
+pub mod sheet;
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+
+use self::ledger::CostMethod;
+use self::lib::{Holding, Portfolio, Transaction, TransactionKind};
+use self::sheet::Row;
+use super::store::Store;
+
+lazy_static! {
+    static ref PORTFOLIOS: Store<HashMap<u64, Portfolio>> = Store::open("portfolios");
+}
+
+pub async fn get_portfolio(user_id: u64) -> Portfolio {
+    PORTFOLIOS
+        .read(|portfolios| portfolios.get(&user_id).cloned().unwrap_or_default())
+        .await
+}
+
+/// Adds a transaction to the user's ledger. A portfolio keeps to the currency of its first
+/// transaction, so costs always add up.
+pub async fn record_transaction(
+    user_id: u64,
+    kind: TransactionKind,
+    coin: &str,
+    amount: Decimal,
+    price: Decimal,
+    time: i64,
+    currency: &str,
+) -> Result<Transaction> {
+    PORTFOLIOS
+        .update(|portfolios| {
+            let portfolio = portfolios.entry(user_id).or_default();
+            set_currency(portfolio, currency)?;
+
+            portfolio.record(kind, coin, amount, price, time)
+        })
+        .await?
+}
+
+/// Adds imported transactions to the user's ledger, all at once or not at all.
+pub async fn import_transactions(user_id: u64, rows: Vec<Row>, currency: &str) -> Result<usize> {
+    PORTFOLIOS
+        .update(|portfolios| {
+            let portfolio = portfolios.entry(user_id).or_default();
+            set_currency(portfolio, currency)?;
+
+            portfolio.import(rows)
+        })
+        .await?
+}
+
+// An empty portfolio takes on the currency of whatever is recorded first
+fn set_currency(portfolio: &mut Portfolio, currency: &str) -> Result<()> {
+    if portfolio.transactions.is_empty() {
+        portfolio.currency = currency.into();
+    } else if portfolio.currency != currency {
+        return Err(anyhow!(
+            "Your portfolio is in {}, record transactions in that currency",
+            portfolio.currency.to_uppercase()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Deletes one of the user's transactions, returning whether it existed.
+pub async fn delete_transaction(user_id: u64, id: u64) -> Result<bool> {
+    PORTFOLIOS
+        .update(|portfolios| match portfolios.get_mut(&user_id) {
+            Some(portfolio) => portfolio.delete(id),
+            None => Ok(false),
+        })
+        .await?
+}
+
+/// The user's holding of `coin`, valued at FIFO cost.
+pub async fn get_holding(user_id: u64, coin: &str) -> Result<Option<Holding>> {
+    let holdings = get_portfolio(user_id)
+        .await
+        .get_holdings(CostMethod::Fifo)?;
+
+    Ok(holdings.into_iter().find(|holding| holding.coin == coin))
+}
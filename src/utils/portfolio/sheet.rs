@@ -0,0 +1,404 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use rusty_money::iso;
+
+use super::lib::{Portfolio, Transaction, TransactionKind};
+use crate::utils::export::Sheet;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+// Formats exchanges commonly write dates in, besides RFC 3339
+const DATE_TIME_FORMATS: [&str; 4] = [
+    DATE_FORMAT,
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+];
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d", "%Y/%m/%d"];
+
+/// The headers of the columns an import reads each field from.
+#[derive(Debug, Clone)]
+pub struct Columns {
+    pub date: String,
+    pub kind: String,
+    pub coin: String,
+    pub amount: String,
+    /// Optional, only transfers out can go without a price
+    pub price: String,
+    /// Optional, rows without one are in the currency the import is made in
+    pub currency: String,
+}
+
+impl Default for Columns {
+    /// The headers an export is written with.
+    fn default() -> Self {
+        Columns {
+            date: "date".into(),
+            kind: "type".into(),
+            coin: "coin".into(),
+            amount: "amount".into(),
+            price: "price".into(),
+            currency: "currency".into(),
+        }
+    }
+}
+
+/// A transaction read from a file, its coin still as written there.
+#[derive(Debug, Clone)]
+pub struct Row {
+    /// The file's line number, to point at in errors
+    pub line: u64,
+    pub kind: TransactionKind,
+    pub coin: String,
+    pub amount: Decimal,
+    pub price: Decimal,
+    pub time: i64,
+    /// The price's currency as a lowercase ISO code, when the file gives one
+    pub currency: Option<String>,
+}
+
+/// The ledger as CSV, oldest transaction first. Its headers are the import's defaults, so an
+/// export can be imported as is.
+pub fn get_ledger_sheet(name: &str, portfolio: &Portfolio) -> Result<Sheet> {
+    let mut transactions: Vec<&Transaction> = portfolio.transactions.iter().collect();
+    transactions.sort_by_key(|tx| (tx.time, tx.id));
+
+    let rows: Vec<(String, &str, &str, String, String, &str)> = transactions
+        .iter()
+        .map(|tx| {
+            (
+                NaiveDateTime::from_timestamp(tx.time, 0)
+                    .format(DATE_FORMAT)
+                    .to_string(),
+                kind_name(tx.kind),
+                tx.coin.as_str(),
+                tx.amount.normalize().to_string(),
+                tx.price.normalize().to_string(),
+                portfolio.currency.as_str(),
+            )
+        })
+        .collect();
+
+    Sheet::from_rows(
+        name,
+        &["date", "type", "coin", "amount", "price", "currency"],
+        &rows,
+    )
+}
+
+/// Reads transactions from CSV with a header row, taking each field from the column
+/// `columns` names. Other columns are ignored. Fails on the first row that can't be read.
+pub fn read_rows(data: &[u8], columns: &Columns) -> Result<Vec<Row>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+    let headers = reader.headers()?.clone();
+
+    let find = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+    };
+    let require = |name: &str| {
+        find(name).ok_or_else(|| {
+            anyhow!(
+                "The file has no `{}` column, point to the right one with the column options",
+                name
+            )
+        })
+    };
+
+    let date = require(&columns.date)?;
+    let kind = require(&columns.kind)?;
+    let coin = require(&columns.coin)?;
+    let amount = require(&columns.amount)?;
+    let price = find(&columns.price);
+    let currency = find(&columns.currency);
+    let now = Utc::now().timestamp();
+
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let cell = |index: usize| record.get(index).unwrap_or_default().trim();
+
+        // Blank lines, such as a trailing one, aren't transactions
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+
+        let row = read_row(
+            line,
+            cell(date),
+            cell(kind),
+            cell(coin),
+            cell(amount),
+            price.map(cell).unwrap_or_default(),
+            now,
+        )
+        .and_then(|row| {
+            Ok(Row {
+                currency: parse_currency(currency.map(cell).unwrap_or_default())?,
+                ..row
+            })
+        })
+        .map_err(|err| anyhow!("Line {}: {}", line, err))?;
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+fn read_row(
+    line: u64,
+    date: &str,
+    kind: &str,
+    coin: &str,
+    amount: &str,
+    price: &str,
+    now: i64,
+) -> Result<Row> {
+    let kind = parse_kind(kind).ok_or_else(|| anyhow!("Unknown transaction type `{}`", kind))?;
+    let time = parse_time(date).ok_or_else(|| anyhow!("Can't read the date `{}`", date))?;
+    if time > now {
+        return Err(anyhow!("The date `{}` hasn't happened yet", date));
+    }
+    if coin.is_empty() {
+        return Err(anyhow!("No coin given"));
+    }
+
+    let amount = parse_number(amount).ok_or_else(|| anyhow!("Can't read the amount"))?;
+    if amount <= Decimal::ZERO {
+        return Err(anyhow!("The amount has to be more than 0"));
+    }
+
+    let price = match (parse_number(price), kind) {
+        (Some(price), _) if price >= Decimal::ZERO => price,
+        (None, TransactionKind::TransferOut) => Decimal::ZERO,
+        _ => return Err(anyhow!("Can't read the price")),
+    };
+
+    Ok(Row {
+        line,
+        kind,
+        coin: coin.into(),
+        amount,
+        price,
+        time,
+        currency: None,
+    })
+}
+
+/// An ISO currency code as stored in a portfolio, or `None` for an empty cell.
+fn parse_currency(currency: &str) -> Result<Option<String>> {
+    match currency {
+        "" => Ok(None),
+        code => match iso::find(&code.to_uppercase()) {
+            Some(currency) => Ok(Some(currency.iso_alpha_code.to_lowercase())),
+            None => Err(anyhow!("Unknown currency `{}`", code)),
+        },
+    }
+}
+
+fn kind_name(kind: TransactionKind) -> &'static str {
+    match kind {
+        TransactionKind::Buy => "buy",
+        TransactionKind::Sell => "sell",
+        TransactionKind::TransferIn => "transfer in",
+        TransactionKind::TransferOut => "transfer out",
+    }
+}
+
+/// Reads a transaction type, including the names exchanges give them.
+fn parse_kind(kind: &str) -> Option<TransactionKind> {
+    let kind = kind.to_lowercase().replace(['_', '-'], " ");
+
+    match kind.trim() {
+        "buy" | "bought" | "purchase" => Some(TransactionKind::Buy),
+        "sell" | "sold" | "sale" => Some(TransactionKind::Sell),
+        "transfer in" | "deposit" | "receive" | "received" => Some(TransactionKind::TransferIn),
+        "transfer out" | "withdrawal" | "withdraw" | "send" | "sent" => {
+            Some(TransactionKind::TransferOut)
+        }
+        _ => None,
+    }
+}
+
+/// Reads a date as a unix timestamp, taking dates without a time at midnight UTC.
+fn parse_time(date: &str) -> Option<i64> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+        return Some(time.timestamp());
+    }
+
+    DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+                .map(|date| date.and_hms(0, 0, 0))
+        })
+        .map(|time| time.timestamp())
+}
+
+/// Reads a number, allowing a currency symbol in front and commas between thousands.
+/// Commas anywhere else, such as a decimal comma in `1.234,56`, make it unreadable.
+fn parse_number(number: &str) -> Option<Decimal> {
+    let number = number
+        .trim_start_matches(|c: char| !c.is_ascii_digit() && c != '.' && c != '-')
+        .trim();
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if fraction.contains(',') {
+        return None;
+    }
+
+    let mut groups = whole.trim_start_matches('-').split(',');
+    let first = groups.next().unwrap_or_default();
+    let grouped = groups.all(|group| group.len() == 3 && !first.is_empty() && first.len() <= 3);
+    if !grouped {
+        return None;
+    }
+
+    let number = number.replace(',', "");
+    Decimal::from_str(&number)
+        .or_else(|_| Decimal::from_scientific(&number))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(csv: &str) -> Result<Vec<Row>> {
+        read_rows(csv.as_bytes(), &Columns::default())
+    }
+
+    fn read_error(csv: &str) -> String {
+        read(csv).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn exports_import_as_they_are() {
+        let mut portfolio = Portfolio {
+            currency: "eur".into(),
+            ..Portfolio::default()
+        };
+        portfolio
+            .record(
+                TransactionKind::Buy,
+                "bitcoin",
+                Decimal::from_str("0.5").unwrap(),
+                Decimal::from_str("20000.25").unwrap(),
+                1_600_000_000,
+            )
+            .unwrap();
+        portfolio
+            .record(
+                TransactionKind::TransferOut,
+                "bitcoin",
+                Decimal::from_str("0.1").unwrap(),
+                Decimal::ZERO,
+                1_600_086_400,
+            )
+            .unwrap();
+
+        let sheet = get_ledger_sheet("test", &portfolio).unwrap();
+        let rows = read_rows(&sheet.data, &Columns::default()).unwrap();
+
+        assert_eq!(rows.len(), portfolio.transactions.len());
+        for (row, tx) in rows.iter().zip(portfolio.transactions.iter()) {
+            assert_eq!(row.kind, tx.kind);
+            assert_eq!(row.coin, tx.coin);
+            assert_eq!(row.amount, tx.amount);
+            assert_eq!(row.price, tx.price);
+            assert_eq!(row.time, tx.time);
+            assert_eq!(row.currency.as_deref(), Some("eur"));
+        }
+    }
+
+    #[test]
+    fn columns_are_found_by_header() {
+        let csv = "Price,Asset,When,Side,Qty,Notes\n\
+                   \"$1,234.50\",ETH,2021-05-01,Bought,2,first\n\
+                   ,ETH,2021/05/02 10:00:00,withdrawal,1,\n";
+        let columns = Columns {
+            date: "when".into(),
+            kind: "side".into(),
+            coin: "asset".into(),
+            amount: "qty".into(),
+            ..Columns::default()
+        };
+        let rows = read_rows(csv.as_bytes(), &columns).unwrap();
+
+        assert_eq!(rows[0].kind, TransactionKind::Buy);
+        assert_eq!(rows[0].price, Decimal::from_str("1234.50").unwrap());
+        assert_eq!(rows[0].time, 1_619_827_200);
+        assert_eq!(rows[0].currency, None);
+        // Transfers out can go without a price
+        assert_eq!(rows[1].kind, TransactionKind::TransferOut);
+        assert_eq!(rows[1].price, Decimal::ZERO);
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        for number in ["1.234,56", "1,23", "12,34.5", "1.5,000", "abc", ""] {
+            assert_eq!(parse_number(number), None, "{}", number);
+        }
+
+        assert_eq!(
+            parse_number("1,234,567.5"),
+            Decimal::from_str("1234567.5").ok()
+        );
+        assert_eq!(parse_number("€ 0.25"), Decimal::from_str("0.25").ok());
+        assert_eq!(parse_number("1e3"), Decimal::from_str("1000").ok());
+
+        let csv = "date,type,coin,amount,price\n2021-05-01,buy,btc,1,\"1.234,56\"\n";
+        assert_eq!(read_error(csv), "Line 2: Can't read the price");
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        for date in ["2021-13-01", "01/05/2021", "yesterday", ""] {
+            assert_eq!(parse_time(date), None, "{}", date);
+        }
+
+        assert_eq!(parse_time("2021-05-01T12:00:00+02:00"), Some(1_619_863_200));
+
+        let csv = "date,type,coin,amount,price\n2021-02-30,buy,btc,1,100\n";
+        assert_eq!(read_error(csv), "Line 2: Can't read the date `2021-02-30`");
+        let csv = "date,type,coin,amount,price\n2999-01-01,buy,btc,1,100\n";
+        assert!(read_error(csv).contains("hasn't happened yet"));
+    }
+
+    #[test]
+    fn malformed_rows_are_rejected() {
+        let csv = "date,type,coin,amount,price\n2021-05-01,airdrop,btc,1,100\n";
+        assert_eq!(
+            read_error(csv),
+            "Line 2: Unknown transaction type `airdrop`"
+        );
+        let csv = "date,type,coin,amount,price\n2021-05-01,buy,btc,-1,100\n";
+        assert_eq!(read_error(csv), "Line 2: The amount has to be more than 0");
+        let csv = "date,type,coin,amount,price\n2021-05-01,sell,btc,1,\n";
+        assert_eq!(read_error(csv), "Line 2: Can't read the price");
+        let csv = "date,type,coin,amount,price,currency\n2021-05-01,buy,btc,1,100,xyz\n";
+        assert_eq!(read_error(csv), "Line 2: Unknown currency `xyz`");
+        assert!(read_error("date,type,coin,price\n").contains("no `amount` column"));
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let csv = "date,type,coin,amount,price\n2021-05-01,buy,btc,1,100\n,,,,\n";
+        assert_eq!(read(csv).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn kinds_include_exchange_names() {
+        assert_eq!(parse_kind("Transfer_In"), Some(TransactionKind::TransferIn));
+        assert_eq!(parse_kind("deposit"), Some(TransactionKind::TransferIn));
+        assert_eq!(parse_kind("SOLD"), Some(TransactionKind::Sell));
+        assert_eq!(parse_kind("stake"), None);
+    }
+}
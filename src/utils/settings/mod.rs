@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::plotter::theme::{Palette, Theme};
+use super::store::Store;
+
+lazy_static! {
+    static ref GUILDS: Store<HashMap<u64, GuildSettings>> = Store::open("guilds");
+}
+
+/// How a guild's charts are drawn. Direct messages always use the defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(default)]
+pub struct GuildSettings {
+    pub palette: Palette,
+    /// Whether line charts use the coin's brand colour instead of green or red
+    pub accent: bool,
+}
+
+impl GuildSettings {
+    /// The chart theme, accented with `brand_color` when the guild has accents on.
+    pub fn get_theme(&self, brand_color: Option<(u8, u8, u8)>) -> Theme {
+        let theme = Theme::new(self.palette);
+
+        match (self.accent, brand_color) {
+            (true, Some(color)) => theme.with_accent(color),
+            _ => theme,
+        }
+    }
+}
+
+pub async fn get_guild_settings(guild_id: Option<u64>) -> GuildSettings {
+    match guild_id {
+        Some(guild_id) => {
+            GUILDS
+                .read(|guilds| guilds.get(&guild_id).copied().unwrap_or_default())
+                .await
+        }
+        None => GuildSettings::default(),
+    }
+}
+
+/// Applies `f` to the guild's settings and returns the result.
+pub async fn update_guild_settings(
+    guild_id: u64,
+    f: impl FnOnce(&mut GuildSettings),
+) -> Result<GuildSettings> {
+    GUILDS
+        .update(|guilds| {
+            let settings = guilds.entry(guild_id).or_default();
+            f(settings);
+            *settings
+        })
+        .await
+}
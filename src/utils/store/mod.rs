@@ -0,0 +1,118 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+
+/// A value persisted as a JSON file in `DATA_DIR` (default: `data`).
+///
+/// Every update is written to a temporary file first and then renamed over the
+/// previous one, so a crash never leaves a half-written store behind. The update only
+/// takes effect in memory once it is on disk.
+pub struct Store<T> {
+    path: PathBuf,
+    data: Mutex<T>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default + Clone> Store<T> {
+    pub fn open(name: &str) -> Self {
+        let dir = PathBuf::from(env::var("DATA_DIR").unwrap_or("data".into()));
+
+        Store::open_at(dir.join(format!("{}.json", name)))
+    }
+
+    /// Starts empty only when there is no file yet. Any other read error panics, as the
+    /// next update would otherwise write the empty store over the real one.
+    fn open_at(path: PathBuf) -> Self {
+        let data = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .unwrap_or_else(|err| panic!("Corrupt store {}: {}", path.display(), err)),
+            Err(err) if err.kind() == ErrorKind::NotFound => T::default(),
+            Err(err) => panic!("Error reading store {}: {}", path.display(), err),
+        };
+
+        Store {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    pub async fn read<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let data = self.data.lock().await;
+        f(&data)
+    }
+
+    /// Applies `f` to a copy and persists it, keeping the stored value as it was if that
+    /// fails.
+    pub async fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R> {
+        let mut data = self.data.lock().await;
+        let mut updated = data.clone();
+        let result = f(&mut updated);
+
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(&updated)?).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        *data = updated;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("koin_bot_store_{}_{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cleanup(dir: &Path) {
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn missing_files_start_empty_and_updates_persist() {
+        let dir = temp_path("persist");
+        let path = dir.join("numbers.json");
+
+        let store: Store<Vec<u32>> = Store::open_at(path.clone());
+        assert!(store.read(|numbers| numbers.is_empty()).await);
+
+        store.update(|numbers| numbers.push(1)).await.unwrap();
+        let reopened: Store<Vec<u32>> = Store::open_at(path);
+        assert_eq!(reopened.read(|numbers| numbers.clone()).await, vec![1]);
+
+        cleanup(&dir);
+    }
+
+    #[tokio::test]
+    async fn failed_writes_leave_the_value_unchanged() {
+        let dir = temp_path("failed_write");
+        let store: Store<Vec<u32>> = Store::open_at(dir.join("blocked").join("numbers.json"));
+        // A file where the store's directory should be, so it can't be written
+        fs::write(dir.join("blocked"), b"").unwrap();
+
+        assert!(store.update(|numbers| numbers.push(1)).await.is_err());
+        assert!(store.read(|numbers| numbers.is_empty()).await);
+
+        cleanup(&dir);
+    }
+
+    #[test]
+    #[should_panic(expected = "Error reading store")]
+    fn unreadable_files_are_not_treated_as_empty() {
+        // A directory can't be read as a file
+        let dir = temp_path("unreadable");
+        let _store: Store<Vec<u32>> = Store::open_at(dir);
+    }
+}
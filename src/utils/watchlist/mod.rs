@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::store::Store;
+
+// Keeps the quote table a readable size
+pub const MAX_WATCHLIST_COINS: usize = 20;
+
+lazy_static! {
+    static ref WATCHLISTS: Store<Watchlists> = Store::open("watchlists");
+}
+
+/// Whose watchlist it is. A server's watchlist is shared by its members.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Owner {
+    User(u64),
+    Guild(u64),
+}
+
+/// Coin ids in the order they were added.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct Watchlists {
+    users: HashMap<u64, Vec<String>>,
+    guilds: HashMap<u64, Vec<String>>,
+}
+
+impl Watchlists {
+    fn get(&self, owner: Owner) -> Option<&Vec<String>> {
+        match owner {
+            Owner::User(id) => self.users.get(&id),
+            Owner::Guild(id) => self.guilds.get(&id),
+        }
+    }
+
+    fn get_mut(&mut self, owner: Owner) -> &mut Vec<String> {
+        match owner {
+            Owner::User(id) => self.users.entry(id).or_default(),
+            Owner::Guild(id) => self.guilds.entry(id).or_default(),
+        }
+    }
+}
+
+pub async fn get_watchlist(owner: Owner) -> Vec<String> {
+    WATCHLISTS
+        .read(|watchlists| watchlists.get(owner).cloned().unwrap_or_default())
+        .await
+}
+
+/// Adds the coins that aren't on the watchlist yet, returning those that were added.
+pub async fn add_coins(owner: Owner, coins: &[String]) -> Result<Vec<String>> {
+    WATCHLISTS
+        .update(|watchlists| {
+            let watchlist = watchlists.get_mut(owner);
+            let mut added: Vec<String> = vec![];
+            for coin in coins {
+                if !watchlist.contains(coin) && !added.contains(coin) {
+                    added.push(coin.clone());
+                }
+            }
+
+            if watchlist.len() + added.len() > MAX_WATCHLIST_COINS {
+                return Err(anyhow!(
+                    "Watchlists hold at most {} coins, remove some first",
+                    MAX_WATCHLIST_COINS
+                ));
+            }
+
+            watchlist.extend(added.iter().cloned());
+            Ok(added)
+        })
+        .await?
+}
+
+/// Takes the coins off the watchlist, returning those that were on it.
+pub async fn remove_coins(owner: Owner, coins: &[String]) -> Result<Vec<String>> {
+    WATCHLISTS
+        .update(|watchlists| {
+            let watchlist = watchlists.get_mut(owner);
+            let removed: Vec<String> = watchlist
+                .iter()
+                .filter(|coin| coins.contains(coin))
+                .cloned()
+                .collect();
+
+            watchlist.retain(|coin| !coins.contains(coin));
+            removed
+        })
+        .await
+}